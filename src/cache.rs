@@ -1,80 +1,608 @@
-use serde::{Deserialize, Serialize};
-use std::fs::{self, File};
+use crate::fsutil;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fs::{self, File, OpenOptions};
 use std::io::{Read, Seek, SeekFrom};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
 
+/// How often `sweep` looks for stale caches.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(86400);
+
+/// Version of the session cache layout. Bump it when a change to `Cache`
+/// would misread an older file, and teach `migrate` to upgrade old files
+/// where that's worth more than recomputing.
+pub const CACHE_VERSION: u64 = 2;
+
+/// A session's cache, one section per collector. Each collector only writes
+/// its own section, through `update`, so concurrent renders don't clobber
+/// each other's results. A section that no longer deserializes starts over
+/// empty instead of discarding the rest.
 #[derive(Serialize, Deserialize, Default)]
 pub struct Cache {
+    #[serde(default)]
+    pub version: u64,
+    // The transcript this cache belongs to; `sweep` drops the cache once it's
+    // gone.
+    #[serde(default)]
+    pub transcript: String,
+    #[serde(default, deserialize_with = "lenient")]
+    pub diff: DiffCache,
+    #[serde(default, deserialize_with = "lenient")]
+    pub process: ProcessCache,
+    #[serde(default, deserialize_with = "lenient")]
+    pub git: GitCache,
+    #[serde(default, deserialize_with = "lenient")]
+    pub vcs: VcsCache,
+}
+
+/// The session diff totals (see `diff::calculate_net_diff`).
+#[derive(Serialize, Deserialize, Default)]
+pub struct DiffCache {
     pub byte_offset: u64,
     pub added: usize,
     pub removed: usize,
     pub files: Vec<String>,
+    // Modification time of each of `files` when the counts were computed. A
+    // file changed since (by hand, a formatter, a checkout) may have drifted
+    // from what the session left, so `drifted` must be re-checked.
+    #[serde(default)]
+    pub file_mtimes: Vec<u128>,
+    #[serde(default)]
+    pub drifted: usize,
+    // Per-transcript shares of `added`/`removed` (see `diff::AgentDiff`).
+    #[serde(default)]
+    pub agents: Vec<crate::diff::AgentDiff>,
+    #[serde(default)]
+    pub file_counts: crate::diff::FileCounts,
     // Combined size of the session's subagent transcripts at the time these
     // counts were computed. The main `byte_offset` only tracks the main
     // transcript, so subagent edits would otherwise leave a stale cache.
     #[serde(default)]
     pub subagent_sig: u64,
+    // Lines in files the diff filter left out, and the filter they were
+    // computed under (see `diff::DiffFilter::signature`).
+    #[serde(default)]
+    pub excluded: usize,
+    // Uuid of the last main-transcript entry read, which the next appended
+    // entry must continue from for the counts to stay valid.
+    #[serde(default)]
+    pub last_uuid: Option<String>,
+    #[serde(default)]
+    pub filter: String,
+    #[serde(default)]
+    pub project: Option<ProjectDiffCache>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct ProcessCache {
     #[serde(default)]
     pub claude_pid: Option<u32>,
 }
 
-pub fn get_cache_path(transcript_path: &str) -> String {
-    let name = std::path::Path::new(transcript_path)
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .unwrap_or("unknown");
-    format!("/tmp/statusline_cache_{}.json", name)
+#[derive(Serialize, Deserialize, Default)]
+pub struct GitCache {
+    #[serde(default)]
+    pub status: Option<GitStatusCache>,
+    #[serde(default)]
+    pub ahead_behind: Option<AheadBehindCache>,
+    #[serde(default)]
+    pub last_commit: Option<LastCommitCache>,
+    #[serde(default)]
+    pub session_base: Option<SessionBaseCache>,
+    #[serde(default)]
+    pub diff: Option<GitDiffCache>,
+    #[serde(default)]
+    pub head_tag: Option<HeadTagCache>,
+}
+
+/// Results from the non-git backends (see `vcs`).
+#[derive(Serialize, Deserialize, Default)]
+pub struct VcsCache {
+    #[serde(default)]
+    pub hg_status: Option<HgStatusCache>,
+    #[serde(default)]
+    pub jj_status: Option<JjStatusCache>,
 }
 
-pub fn load(cache_path: &str, transcript_path: &str, subagent_sig: u64) -> Option<Cache> {
-    let content = fs::read_to_string(cache_path).ok()?;
-    let cache: Cache = serde_json::from_str(&content).ok()?;
+fn lenient<'de, D: Deserializer<'de>, T: DeserializeOwned + Default>(d: D) -> Result<T, D::Error> {
+    let v = serde_json::Value::deserialize(d)?;
+    Ok(serde_json::from_value(v).unwrap_or_default())
+}
 
-    if !cache.files.iter().all(|f| std::path::Path::new(f).exists()) {
-        return None;
+/// Upgrades a cache written in an older layout to `CACHE_VERSION`. False
+/// when there's no upgrade path, and the cache is discarded.
+fn migrate(v: &mut serde_json::Value) -> bool {
+    let Some(obj) = v.as_object_mut() else { return false };
+    let version = obj.get("version").and_then(|x| x.as_u64()).unwrap_or(1);
+    if version == 1 {
+        // Version 1 kept every collector's fields at the top level.
+        let mut take = |keys: &[(&str, &str)]| {
+            let mut section = serde_json::Map::new();
+            for (old, new) in keys {
+                if let Some(x) = obj.remove(*old) {
+                    section.insert(new.to_string(), x);
+                }
+            }
+            serde_json::Value::Object(section)
+        };
+        let diff = take(&[
+            ("byte_offset", "byte_offset"), ("added", "added"), ("removed", "removed"),
+            ("files", "files"), ("file_mtimes", "file_mtimes"), ("drifted", "drifted"),
+            ("agents", "agents"), ("file_counts", "file_counts"), ("subagent_sig", "subagent_sig"),
+            ("excluded", "excluded"), ("last_uuid", "last_uuid"), ("filter", "filter"),
+            ("project_diff", "project"),
+        ]);
+        let process = take(&[("claude_pid", "claude_pid")]);
+        let git = take(&[
+            ("git_status", "status"), ("ahead_behind", "ahead_behind"), ("last_commit", "last_commit"),
+            ("session_base", "session_base"), ("git_diff", "diff"),
+        ]);
+        obj.insert("diff".to_string(), diff);
+        obj.insert("process".to_string(), process);
+        obj.insert("git".to_string(), git);
+        obj.insert("version".to_string(), CACHE_VERSION.into());
+        return true;
     }
+    version == CACHE_VERSION
+}
+
+/// Last `git-status` counts, reusable while the repo's index mtime and the
+/// transcript size both match and they're recent (see `git::get_git_status`).
+#[derive(Serialize, Deserialize, Default)]
+pub struct GitStatusCache {
+    pub work_tree: String,
+    pub index_mtime_ms: u128,
+    pub transcript_size: u64,
+    // When git was last asked, in ms since the epoch.
+    #[serde(default)]
+    pub checked_ms: u128,
+    // None when `git status` failed or timed out.
+    pub status: Option<crate::git::GitStatus>,
+}
+
+/// Last `hg status` dirty flag, reusable on the same terms as
+/// `GitStatusCache`, with the dirstate standing in for the index.
+#[derive(Serialize, Deserialize, Default)]
+pub struct HgStatusCache {
+    pub root: String,
+    pub dirstate_mtime_ms: u128,
+    pub transcript_size: u64,
+    pub checked_ms: u128,
+    pub dirty: bool,
+}
+
+/// Last `jj log` result for `@` (None when it failed), reusable while the
+/// op heads are unchanged and it's recent (see `vcs::jj_status`).
+#[derive(Serialize, Deserialize, Default)]
+pub struct JjStatusCache {
+    pub root: String,
+    pub op_heads_mtime_ms: u128,
+    pub checked_ms: u128,
+    pub status: Option<crate::vcs::JjStatus>,
+}
+
+/// `$XDG_CACHE_HOME/statusline` (`~/.cache/statusline` by default), created
+/// private to the user on first use so other local users can neither read
+/// the paths it records nor plant a cache for us to load. None without a
+/// home directory, or when the directory can't be made private (it belongs
+/// to someone else).
+pub fn cache_dir() -> Option<&'static Path> {
+    static DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+    DIR.get_or_init(|| {
+        let base = std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".cache")))?;
+        let dir = base.join("statusline");
+        fs::DirBuilder::new().recursive(true).mode(0o700).create(&dir).ok()?;
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700)).ok()?;
+        Some(dir)
+    })
+    .as_deref()
+}
+
+/// The session's cache, named after the transcript's file stem (the session id).
+fn cache_path(transcript_path: &str) -> Option<PathBuf> {
+    Some(cache_dir()?.join(format!("session-{}.json", transcript_stem(transcript_path))))
+}
+
+/// Where the session's incremental diff state (`diff::DiffState`) lives. It
+/// holds file snapshots, so it's kept out of the main cache that every render
+/// reads.
+fn diff_state_path(transcript_path: &str) -> Option<PathBuf> {
+    Some(cache_dir()?.join(format!("diff-{}.json", transcript_stem(transcript_path))))
+}
 
-    if has_new_file_ops(transcript_path, cache.byte_offset) {
+fn transcript_stem(transcript_path: &str) -> &str {
+    Path::new(transcript_path).file_stem().and_then(|s| s.to_str()).unwrap_or("unknown")
+}
+
+fn load_json<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    let content = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn save_json<T: Serialize>(path: &Path, value: &T) {
+    if let Ok(content) = serde_json::to_string(value) {
+        let _ = fsutil::atomic_write(path, content.as_bytes());
+    }
+}
+
+pub fn load_diff_state<T: DeserializeOwned>(transcript_path: &str) -> Option<T> {
+    load_json(&diff_state_path(transcript_path)?)
+}
+
+pub fn save_diff_state<T: Serialize>(transcript_path: &str, state: &T) {
+    if let Some(path) = diff_state_path(transcript_path) {
+        save_json(&path, state);
+    }
+}
+
+/// Where the records of a project's recent transcripts live
+/// (`diff::ProjectState`), named after the project root with `/` turned
+/// into `-`, as Claude Code names its project directories.
+fn project_state_path(root: &Path) -> Option<PathBuf> {
+    let name = root.to_string_lossy().replace('/', "-");
+    Some(cache_dir()?.join(format!("project-{}.json", name)))
+}
+
+pub fn load_project_state<T: DeserializeOwned>(root: &Path) -> Option<T> {
+    load_json(&project_state_path(root)?)
+}
+
+pub fn save_project_state<T: Serialize>(root: &Path, state: &T) {
+    if let Some(path) = project_state_path(root) {
+        save_json(&path, state);
+    }
+}
+
+/// The project root each recent session ran in (see `diff::project_sessions`).
+pub fn load_project_roots<T: DeserializeOwned>() -> Option<T> {
+    load_json(&cache_dir()?.join("project-roots.json"))
+}
+
+pub fn save_project_roots<T: Serialize>(roots: &T) {
+    if let Some(dir) = cache_dir() {
+        save_json(&dir.join("project-roots.json"), roots);
+    }
+}
+
+/// The session's diff totals, if they're still valid for the transcript and
+/// its subagents as they are now.
+pub fn load_diff(transcript_path: &str, subagent_sig: u64) -> Option<DiffCache> {
+    let mut diff = load_raw(transcript_path)?.diff;
+
+    if !diff.files.iter().all(|f| Path::new(f).exists()) {
         return None;
     }
+    if diff.file_mtimes.len() != diff.files.len()
+        || diff.files.iter().zip(&diff.file_mtimes).any(|(f, &m)| fsutil::mtime_ms(Path::new(f)) != Some(m)) {
+            return None;
+        }
 
     // A subagent transcript changed size (a new one appeared or an existing one
     // grew): its edits aren't reflected in the cached counts, so recompute.
-    if cache.subagent_sig != subagent_sig {
+    if diff.subagent_sig != subagent_sig {
         return None;
     }
 
-    Some(cache)
+    let (offset, last_uuid) = skip_harmless_lines(transcript_path, diff.byte_offset, diff.last_uuid.clone())?;
+    if offset != diff.byte_offset {
+        // Nothing appended affects the counts; remember that so the next
+        // render doesn't re-check the same lines. Unless another render
+        // recomputed them meanwhile.
+        let read = diff.byte_offset;
+        update(transcript_path, |c| {
+            if c.diff.byte_offset == read {
+                c.diff.byte_offset = offset;
+                c.diff.last_uuid = last_uuid.clone();
+            }
+        });
+        diff.byte_offset = offset;
+        diff.last_uuid = last_uuid;
+    }
+    Some(diff)
 }
 
-pub fn load_raw(cache_path: &str) -> Option<Cache> {
-    let content = fs::read_to_string(cache_path).ok()?;
-    serde_json::from_str(&content).ok()
+pub fn load_raw(transcript_path: &str) -> Option<Cache> {
+    read_cache(&cache_path(transcript_path)?)
 }
 
-pub fn save(cache_path: &str, cache: &Cache) {
-    if let Ok(content) = serde_json::to_string(cache) {
-        let _ = fs::write(cache_path, content);
+/// Reads a session cache, upgrading an older layout (see `migrate`). None
+/// when it's missing, unreadable, or from a layout that can't be upgraded.
+fn read_cache(path: &Path) -> Option<Cache> {
+    let mut v: serde_json::Value = load_json(path)?;
+    if !migrate(&mut v) {
+        return None;
     }
+    serde_json::from_value(v).ok()
 }
 
-pub fn get_file_size(path: &str) -> u64 {
-    fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+/// Applies `f` to the session's cache and writes it back. The cache is
+/// re-read under an exclusive advisory lock on a `.lock` file beside it, as
+/// `usage` locks its cache, so renders running at once each keep the
+/// sections the others wrote. Waits for the lock, which is only held for the
+/// read and write. The write is atomic, so readers that don't lock never see
+/// half a cache.
+pub fn update(transcript_path: &str, f: impl FnOnce(&mut Cache)) {
+    let Some(path) = cache_path(transcript_path) else { return };
+    let Ok(lock_file) = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path.with_extension("lock")) else { return };
+    if lock_file.lock().is_err() {
+        return;
+    }
+    let mut cache = read_cache(&path).unwrap_or_default();
+    f(&mut cache);
+    cache.version = CACHE_VERSION;
+    cache.transcript = transcript_path.to_string();
+    save_json(&path, &cache);
 }
 
-fn has_new_file_ops(transcript_path: &str, byte_offset: u64) -> bool {
-    let mut file = match File::open(transcript_path) {
-        Ok(f) => f,
-        Err(_) => return true,
-    };
+/// Deletes the caches and diff states older versions left in `/tmp`, where
+/// other local users could read the paths and file contents they hold. Only
+/// ours go (the cache dir's owner is us), and only once, tracked by a marker
+/// file.
+fn remove_tmp_caches(dir: &Path) {
+    let marker = dir.join(".tmp-caches-removed");
+    if marker.exists() {
+        return;
+    }
+    let Ok(uid) = fs::metadata(dir).map(|m| m.uid()) else { return };
+    let Ok(entries) = fs::read_dir("/tmp") else { return };
+    for e in entries.flatten() {
+        let Some(name) = e.file_name().to_str().map(String::from) else { continue };
+        let legacy = (name.starts_with("statusline_cache_") || name.starts_with("statusline_diff_")) && name.ends_with(".json");
+        // Not followed: a symlink planted under that name isn't ours to judge.
+        if legacy && e.metadata().is_ok_and(|m| m.is_file() && m.uid() == uid) {
+            let _ = fs::remove_file(e.path());
+        }
+    }
+    let _ = fs::write(&marker, "");
+}
 
-    if file.seek(SeekFrom::Start(byte_offset)).is_err() {
-        return true;
+/// Deletes session caches whose transcript no longer exists and any cache
+/// file not written in `max_age_days` (0 keeps them however old). Runs at
+/// most once a day, tracked by the mtime of a marker file in the cache dir.
+pub fn sweep(max_age_days: u64) {
+    let Some(dir) = cache_dir() else { return };
+    let marker = dir.join(".last-sweep");
+    let now = SystemTime::now();
+    if fs::metadata(&marker)
+        .and_then(|m| m.modified())
+        .is_ok_and(|t| now.duration_since(t).is_ok_and(|age| age < SWEEP_INTERVAL)) {
+            return;
+        }
+    let _ = fs::write(&marker, "");
+    remove_tmp_caches(dir);
+
+    let max_age = (max_age_days > 0).then(|| Duration::from_secs(max_age_days * 86400));
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for e in entries.flatten() {
+        let path = e.path();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else { continue };
+        if name.starts_with('.') {
+            continue;
+        }
+        // Lock files go with their cache; one in use is never written, so
+        // its age says nothing. One left without a cache (`cache clear`)
+        // goes once it's older than a sweep interval.
+        if let Some(stem) = name.strip_prefix("session-").and_then(|n| n.strip_suffix(".lock")) {
+            let stale = e.metadata()
+                .and_then(|m| m.modified())
+                .is_ok_and(|t| now.duration_since(t).is_ok_and(|age| age > SWEEP_INTERVAL));
+            if stale && !dir.join(format!("session-{}.json", stem)).exists() {
+                let _ = fs::remove_file(&path);
+            }
+            continue;
+        }
+        if name.ends_with(".lock") {
+            continue;
+        }
+        let expired = max_age.is_some_and(|max| {
+            e.metadata()
+                .and_then(|m| m.modified())
+                .is_ok_and(|t| now.duration_since(t).is_ok_and(|age| age > max))
+        });
+        let orphaned = name.starts_with("session-")
+            && read_cache(&path).is_some_and(|c| !c.transcript.is_empty() && !Path::new(&c.transcript).exists());
+        if !expired && !orphaned {
+            continue;
+        }
+        let _ = fs::remove_file(&path);
+        // The diff state and lock belong to the session cache of the same name.
+        if let Some(stem) = name.strip_prefix("session-").and_then(|n| n.strip_suffix(".json")) {
+            let _ = fs::remove_file(dir.join(format!("diff-{}.json", stem)));
+            let _ = fs::remove_file(dir.join(format!("session-{}.lock", stem)));
+        }
+    }
+}
+
+/// One session's cache, for `statusline cache status`.
+pub struct SessionEntry {
+    /// The transcript's file stem, which names the cache files.
+    pub id: String,
+    pub transcript: String,
+    pub byte_offset: u64,
+    /// Files the cached diff total covers.
+    pub files: usize,
+    pub claude_pid: Option<u32>,
+}
+
+/// Every session cache in the cache dir, by id. Caches that can't be read
+/// are listed with what's known: their id.
+pub fn sessions() -> Vec<SessionEntry> {
+    let Some(dir) = cache_dir() else { return Vec::new() };
+    let Ok(entries) = fs::read_dir(dir) else { return Vec::new() };
+    let mut out: Vec<SessionEntry> = entries
+        .flatten()
+        .filter_map(|e| {
+            let name = e.file_name().to_str()?.to_string();
+            let id = name.strip_prefix("session-")?.strip_suffix(".json")?.to_string();
+            let cache = read_cache(&e.path()).unwrap_or_default();
+            Some(SessionEntry {
+                id,
+                transcript: cache.transcript,
+                byte_offset: cache.diff.byte_offset,
+                files: cache.diff.files.len(),
+                claude_pid: cache.process.claude_pid,
+            })
+        })
+        .collect();
+    out.sort_by(|a, b| a.id.cmp(&b.id));
+    out
+}
+
+/// Deletes the cache and diff state of session `id`, or of every session
+/// when None, and returns how many sessions went. Each session's lock is
+/// held while its files are removed, so a render updating it finishes
+/// first. The lock file itself stays: a render waiting on it must lock the
+/// same file as the next one. `sweep` removes it once it's stale.
+pub fn clear_sessions(id: Option<&str>) -> usize {
+    let ids: Vec<String> = match id {
+        Some(id) => vec![id.to_string()],
+        None => sessions().into_iter().map(|s| s.id).collect(),
+    };
+    let Some(dir) = cache_dir() else { return 0 };
+    let mut removed = 0;
+    for id in ids {
+        let cache = dir.join(format!("session-{}.json", id));
+        let lock = dir.join(format!("session-{}.lock", id));
+        let lock_file = OpenOptions::new().write(true).create(true).truncate(false).open(&lock);
+        if let Ok(f) = &lock_file {
+            let _ = f.lock();
+        }
+        let had_cache = fs::remove_file(&cache).is_ok();
+        let had_state = fs::remove_file(dir.join(format!("diff-{}.json", id))).is_ok();
+        removed += (had_cache || had_state) as usize;
     }
+    removed
+}
 
+/// The `diff-today` caches (`project-*.json`, including the roots index)
+/// with their sizes, for `statusline cache status`.
+pub fn project_caches() -> Vec<(PathBuf, u64)> {
+    let Some(dir) = cache_dir() else { return Vec::new() };
+    let Ok(entries) = fs::read_dir(dir) else { return Vec::new() };
+    let mut out: Vec<(PathBuf, u64)> = entries
+        .flatten()
+        .filter(|e| e.file_name().to_str().is_some_and(|n| n.starts_with("project-") && n.ends_with(".json")))
+        .map(|e| (e.path(), e.metadata().map(|m| m.len()).unwrap_or(0)))
+        .collect();
+    out.sort();
+    out
+}
+
+/// Deletes the `diff-today` caches and returns how many went. They hold the
+/// edit records of every recent transcript; the next render reads them
+/// again.
+pub fn clear_projects() -> usize {
+    project_caches().iter().filter(|(path, _)| fs::remove_file(path).is_ok()).count()
+}
+
+pub fn get_file_size(path: &str) -> u64 {
+    fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+#[derive(Deserialize)]
+struct TreeLinks {
+    uuid: Option<String>,
+    #[serde(rename = "parentUuid")]
+    parent_uuid: Option<String>,
+}
+
+/// Reads the complete lines appended after `byte_offset` and returns the new
+/// offset and last uuid if none of them can change the diff: no file edits
+/// or Bash deletions and renames, and every new entry continues from the one
+/// before it. An entry that branches off anywhere else may be a rewind that
+/// abandons counted edits.
+fn skip_harmless_lines(transcript_path: &str, byte_offset: u64, mut last_uuid: Option<String>) -> Option<(u64, Option<String>)> {
+    let mut file = File::open(transcript_path).ok()?;
+    file.seek(SeekFrom::Start(byte_offset)).ok()?;
     let mut new_content = String::new();
-    if file.read_to_string(&mut new_content).is_err() {
-        return true;
+    file.read_to_string(&mut new_content).ok()?;
+
+    let mut offset = byte_offset;
+    for line in new_content.split_inclusive('\n') {
+        if !line.ends_with('\n') {
+            break; // still being written
+        }
+        if line.contains("\"filePath\"") || line.contains("\"notebook_path\"") || crate::diff::has_file_ops(line) {
+            return None;
+        }
+        if line.contains("\"uuid\"")
+            && let Ok(links) = serde_json::from_str::<TreeLinks>(line)
+            && let Some(uuid) = links.uuid {
+                if last_uuid.is_some() && links.parent_uuid != last_uuid {
+                    return None;
+                }
+                last_uuid = Some(uuid);
+            }
+        offset += line.len() as u64;
     }
+    Some((offset, last_uuid))
+}
+
+/// Last `ahead-behind` counts for a pair of branch tips. Commits are immutable,
+/// so the counts stay valid for as long as both SHAs are unchanged.
+#[derive(Serialize, Deserialize, Default)]
+pub struct AheadBehindCache {
+    pub local: String,
+    pub upstream: String,
+    pub ahead: usize,
+    pub behind: usize,
+}
 
-    new_content.contains("\"filePath\"")
+/// HEAD's subject and commit time for the commit with this SHA.
+#[derive(Serialize, Deserialize, Default)]
+pub struct LastCommitCache {
+    pub sha: String,
+    pub subject: String,
+    pub time: i64,
+}
+
+/// The tag `git tag --points-at` gave for a detached HEAD at this SHA, if
+/// any.
+#[derive(Serialize, Deserialize, Default)]
+pub struct HeadTagCache {
+    pub sha: String,
+    pub tag: Option<String>,
+}
+
+/// The commit HEAD pointed to the first time the session's diff was shown,
+/// for `diff.mode: git-session-base`.
+#[derive(Serialize, Deserialize, Default)]
+pub struct SessionBaseCache {
+    pub work_tree: String,
+    pub sha: String,
+}
+
+/// Last per-file numstat against `base`, reusable while the index mtime and
+/// the transcript size both match and it's recent, as for `GitStatusCache`.
+#[derive(Serialize, Deserialize, Default)]
+pub struct GitDiffCache {
+    pub work_tree: String,
+    pub base: String,
+    pub index_mtime_ms: u128,
+    pub transcript_size: u64,
+    #[serde(default)]
+    pub checked_ms: u128,
+    pub files: Vec<(String, usize, usize)>,
+}
+
+/// Last `diff-today` totals, reusable while the cutoff, the filter and the
+/// combined size of the project's transcripts all match (see
+/// `diff::project_totals`).
+#[derive(Serialize, Deserialize, Default)]
+pub struct ProjectDiffCache {
+    pub since: String,
+    pub filter: String,
+    pub sig: u64,
+    pub added: usize,
+    pub removed: usize,
 }
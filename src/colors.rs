@@ -10,6 +10,7 @@ fn ansi(color: Color) -> String {
 
 pub struct Colors {
     pub dir: String,
+    pub path: String,
     pub branch: String,
     pub added: String,
     pub removed: String,
@@ -21,6 +22,22 @@ pub struct Colors {
     pub cost: String,
     pub rate_5h: String,
     pub rate_7d: String,
+    pub git_status: String,
+    pub ahead_behind: String,
+    pub git_state: String,
+    pub worktree: String,
+    pub submodule: String,
+    pub last_commit: String,
+    pub commit_age: String,
+    pub stash: String,
+    pub diff_excluded: String,
+    pub diff_drift: String,
+    pub diff_agents: String,
+    pub diff_today: String,
+    pub files: String,
+    pub files_new: String,
+    pub files_deleted: String,
+    pub files_outside_repo: String,
     pub sep: String,
 }
 
@@ -92,6 +109,7 @@ fn lookup(flavor: &Flavor, color_map: &HashMap<String, String>, key: &str, defau
 pub fn resolve_colors(flavor: &Flavor, color_map: &HashMap<String, String>) -> Colors {
     Colors {
         dir: lookup(flavor, color_map, "dir", flavor.colors.teal),
+        path: lookup(flavor, color_map, "path", flavor.colors.teal),
         branch: lookup(flavor, color_map, "branch", flavor.colors.blue),
         added: lookup(flavor, color_map, "added", flavor.colors.green),
         removed: lookup(flavor, color_map, "removed", flavor.colors.red),
@@ -103,6 +121,22 @@ pub fn resolve_colors(flavor: &Flavor, color_map: &HashMap<String, String>) -> C
         cost: lookup(flavor, color_map, "cost", flavor.colors.green),
         rate_5h: lookup(flavor, color_map, "rate-5h", flavor.colors.subtext0),
         rate_7d: lookup(flavor, color_map, "rate-7d", flavor.colors.subtext0),
+        git_status: lookup(flavor, color_map, "git-status", flavor.colors.yellow),
+        ahead_behind: lookup(flavor, color_map, "ahead-behind", flavor.colors.sky),
+        git_state: lookup(flavor, color_map, "git-state", flavor.colors.red),
+        worktree: lookup(flavor, color_map, "worktree", flavor.colors.lavender),
+        submodule: lookup(flavor, color_map, "submodule", flavor.colors.lavender),
+        last_commit: lookup(flavor, color_map, "last-commit", flavor.colors.subtext1),
+        commit_age: lookup(flavor, color_map, "commit-age", flavor.colors.subtext0),
+        stash: lookup(flavor, color_map, "stash", flavor.colors.flamingo),
+        diff_excluded: lookup(flavor, color_map, "diff-excluded", flavor.colors.overlay1),
+        diff_drift: lookup(flavor, color_map, "diff-drift", flavor.colors.maroon),
+        diff_agents: lookup(flavor, color_map, "diff-agents", flavor.colors.subtext1),
+        diff_today: lookup(flavor, color_map, "diff-today", flavor.colors.sky),
+        files: lookup(flavor, color_map, "files", flavor.colors.subtext1),
+        files_new: lookup(flavor, color_map, "files-new", flavor.colors.green),
+        files_deleted: lookup(flavor, color_map, "files-deleted", flavor.colors.red),
+        files_outside_repo: lookup(flavor, color_map, "files-touched-outside-repo", flavor.colors.peach),
         sep: lookup(flavor, color_map, "separator", flavor.colors.text),
     }
 }
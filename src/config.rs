@@ -10,6 +10,132 @@ pub struct Config {
     pub colors: HashMap<String, String>,
     #[serde(default)]
     pub track_usage: TrackUsageConfig,
+    #[serde(default)]
+    pub git: GitConfig,
+    #[serde(default)]
+    pub path: PathConfig,
+    #[serde(default)]
+    pub diff: DiffConfig,
+    #[serde(default)]
+    pub cache: CacheConfig,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CacheConfig {
+    /// Days a session's cache may go unwritten before it's deleted; 0 keeps
+    /// caches until their transcript is gone.
+    #[serde(default = "default_cache_max_age_days")]
+    pub max_age_days: u64,
+}
+
+fn default_cache_max_age_days() -> u64 {
+    30
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig { max_age_days: default_cache_max_age_days() }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DiffConfig {
+    /// What `diff` measures: `transcript` (the session's own edits, from its
+    /// transcript), `git-head` (the working tree against HEAD) or
+    /// `git-session-base` (against the commit HEAD was at when the session
+    /// started).
+    #[serde(default = "default_diff_mode")]
+    pub mode: String,
+    /// Globs a file must match to count toward `diff`; empty counts everything.
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Globs for files left out of `diff` (lockfiles, snapshots, generated code).
+    /// A pattern without `/` matches the file name; `/...` and `~/...` match
+    /// the absolute path; anything else matches the path from the repo root.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Count only files under the repository containing the session's cwd
+    /// (or under the cwd itself outside a repository).
+    #[serde(default)]
+    pub repo_only: bool,
+    /// Count files changed outside the session since its last edit (see the
+    /// `diff-drift` field) by their content on disk against the session's
+    /// starting state, instead of by the session's own edits.
+    #[serde(default)]
+    pub on_disk: bool,
+}
+
+fn default_diff_mode() -> String {
+    "transcript".to_string()
+}
+
+impl Default for DiffConfig {
+    fn default() -> Self {
+        DiffConfig {
+            mode: default_diff_mode(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            repo_only: false,
+            on_disk: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PathConfig {
+    /// `full`, `fish` (abbreviate leading components to one letter) or `last`
+    /// (drop leading components).
+    #[serde(default = "default_path_style")]
+    pub style: String,
+    /// Trailing components `fish` and `last` leave intact.
+    #[serde(default = "default_path_keep")]
+    pub keep: usize,
+    /// Hard cap in characters, applied after the style by eliding from the left.
+    #[serde(default)]
+    pub max_width: Option<usize>,
+}
+
+fn default_path_style() -> String {
+    "full".to_string()
+}
+
+fn default_path_keep() -> usize {
+    2
+}
+
+impl Default for PathConfig {
+    fn default() -> Self {
+        PathConfig { style: default_path_style(), keep: default_path_keep(), max_width: None }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct GitConfig {
+    /// Per-host overrides keyed by the remote's host name (as it appears in
+    /// the clone URL), for self-hosted GitLab/Gitea instances whose web UI
+    /// lives somewhere other than `https://<host>/<path>`.
+    #[serde(default)]
+    pub hosts: HashMap<String, HostConfig>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct HostConfig {
+    /// Web URL template for the repository; `{host}` and `{path}` (owner/repo,
+    /// without `.git`) are substituted.
+    #[serde(default)]
+    pub web_url: Option<String>,
+    /// Forge type for link layout: `github`, `gitlab`, `bitbucket` or `gitea`.
+    /// Guessed from the host name when unset.
+    #[serde(default)]
+    pub kind: Option<String>,
+    /// Link template overrides; `{repo}` is the web URL, plus `{branch}`,
+    /// `{sha}` or `{base}` as applicable.
+    #[serde(default)]
+    pub branch_url: Option<String>,
+    #[serde(default)]
+    pub commit_url: Option<String>,
+    #[serde(default)]
+    pub compare_url: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Default)]
@@ -58,9 +184,21 @@ pub fn default_config() -> Config {
             ("tokens-percent", "peach"), ("cost", "green"),
             ("cpu", "subtext0"), ("ram", "subtext0"),
             ("rate-5h", "subtext0"), ("rate-7d", "subtext0"),
-            ("separator", "text"),
+            ("git-status", "yellow"), ("ahead-behind", "sky"),
+            ("git-state", "red"), ("worktree", "lavender"),
+            ("submodule", "lavender"), ("path", "teal"),
+            ("last-commit", "subtext1"), ("commit-age", "subtext0"),
+            ("stash", "flamingo"), ("diff-excluded", "overlay1"),
+            ("diff-drift", "maroon"), ("diff-agents", "subtext1"),
+            ("diff-today", "sky"), ("files", "subtext1"),
+            ("files-new", "green"), ("files-deleted", "red"),
+            ("files-touched-outside-repo", "peach"), ("separator", "text"),
         ].iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
         track_usage: TrackUsageConfig::default(),
+        git: GitConfig::default(),
+        path: PathConfig::default(),
+        diff: DiffConfig::default(),
+        cache: CacheConfig::default(),
     }
 }
 
@@ -107,8 +245,26 @@ pub fn write_default_config() {
 pub fn print_fields() {
     println!("Fields:");
     println!("  dir              project directory (link-wrapped if remote URL exists)");
-    println!("  branch           git branch name");
-    println!("  diff             lines added/removed (uses 'added' and 'removed' colors)");
+    println!("  path             cwd relative to the repo root (monorepo/packages/api/src)");
+    println!("  branch           git branch (tag or detached@sha when detached), with any in-progress operation;");
+    println!("                   jj/hg bookmark or branch (@changeid if unnamed), * when dirty");
+    println!("  worktree         linked worktree name (wt:name), hidden in the main checkout");
+    println!("  submodule        superproject and path when inside a submodule (sub:monorepo/libs/x)");
+    println!("  last-commit      HEAD's commit subject");
+    println!("  commit-age       time since HEAD was committed (12m)");
+    println!("  git-state        in-progress rebase/merge/cherry-pick/revert/bisect (REBASE 3/7)");
+    println!("  git-status       staged/modified/untracked/conflicted counts (+3 ~2 ?1 !1)");
+    println!("  ahead-behind     commits ahead/behind the branch's upstream (↑2 ↓5, ⚠ gone if deleted)");
+    println!("  stash            number of stash entries (stash:3)");
+    println!("  diff             lines added/removed (uses 'added' and 'removed' colors; linked to compare view)");
+    println!("  diff-excluded    lines left out of diff by diff.include/exclude/repo_only (excluded:42)");
+    println!("  files            files with a net change this session (7 files)");
+    println!("  files-new        files the session created (3 new)");
+    println!("  files-deleted    pre-existing files the session deleted (1 deleted)");
+    println!("  files-touched-outside-repo  files edited outside the cwd's repository (2 outside repo)");
+    println!("  diff-agents      diff split between main agent and subagents (main +120 -30 · 3 agents +400 -12)");
+    println!("  diff-today       net lines changed today across every session in the repository (today +340 -120)");
+    println!("  diff-drift       files changed on disk since the session last edited them (drift:2)");
     println!("  model            Claude model name");
     println!("  tokens           token usage bar (50k/200k)");
     println!("  tokens-percent   token usage bar (15% · 200k)");
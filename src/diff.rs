@@ -1,11 +1,17 @@
 use crate::cache;
+use crate::config::DiffConfig;
 use crate::fsutil;
-use serde::Deserialize;
+use crate::git;
+use crate::shell::{self, FileOp};
+use crate::usage;
+use serde::{Deserialize, Serialize};
 use similar::{ChangeTag, TextDiff};
 use std::borrow::Cow;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsStr;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::fs::{self, File};
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
 
 #[derive(Deserialize)]
@@ -14,27 +20,56 @@ struct LineEntry {
     #[serde(rename = "parentUuid")]
     parent_uuid: Option<String>,
     timestamp: Option<String>,
+    cwd: Option<String>,
     // Error results are plain strings, so parse lazily from a Value.
     #[serde(rename = "toolUseResult")]
     tool_use_result: Option<serde_json::Value>,
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 struct ToolUseResult {
-    #[serde(rename = "filePath")]
+    // NotebookEdit names the file `notebook_path`.
+    #[serde(rename = "filePath", alias = "notebook_path")]
     file_path: Option<String>,
-    #[serde(rename = "originalFile")]
+    // MultiEdit carries the pre-edit snapshot as `originalFileContents`.
+    #[serde(rename = "originalFile", alias = "originalFileContents")]
     original_file: Option<String>,
     content: Option<String>,
     #[serde(rename = "oldString")]
     old_string: Option<String>,
     #[serde(rename = "newString")]
     new_string: Option<String>,
+    /// MultiEdit: replacements applied in order, each to the previous result.
+    edits: Option<Vec<EditPair>>,
     #[serde(rename = "structuredPatch")]
     structured_patch: Option<Vec<Hunk>>,
+    #[serde(flatten)]
+    notebook: NotebookEdit,
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
+struct EditPair {
+    old_string: String,
+    new_string: String,
+}
+
+/// NotebookEdit's result fields. The whole-notebook snapshots are only
+/// present in newer transcripts.
+#[derive(Serialize, Deserialize, Default, Clone)]
+struct NotebookEdit {
+    edit_mode: Option<String>,
+    new_source: Option<String>,
+    original_file: Option<String>,
+    updated_file: Option<String>,
+}
+
+impl ToolUseResult {
+    fn is_notebook_edit(&self) -> bool {
+        self.notebook.edit_mode.is_some()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 struct Hunk {
     #[serde(rename = "oldStart")]
     old_start: usize,
@@ -43,34 +78,120 @@ struct Hunk {
     lines: Vec<String>,
 }
 
-/// A surviving file-edit record: (file path, timestamp, tool result).
-type Record = (String, Option<String>, ToolUseResult);
+/// A surviving record that changes files: (timestamp, change).
+type Record = (Option<String>, Change);
+
+#[derive(Serialize, Deserialize, Clone)]
+enum Change {
+    /// A Write, Edit, MultiEdit or NotebookEdit result for a file.
+    Edit(String, Box<ToolUseResult>),
+    /// An `rm` or `mv` run through the Bash tool.
+    Shell(FileOp),
+}
+
+/// An assistant entry's tool calls; only Bash commands are of interest.
+#[derive(Deserialize)]
+struct AssistantEntry {
+    message: AssistantMessage,
+}
+
+#[derive(Deserialize)]
+struct AssistantMessage {
+    content: Vec<ContentBlock>,
+}
+
+#[derive(Deserialize)]
+struct ContentBlock {
+    name: Option<String>,
+    input: Option<BashInput>,
+}
+
+#[derive(Deserialize)]
+struct BashInput {
+    command: Option<String>,
+}
+
+/// The file operations in a transcript line's Bash tool calls.
+fn bash_file_ops(line: &str, cwd: Option<&Path>) -> Vec<FileOp> {
+    let Ok(entry) = serde_json::from_str::<AssistantEntry>(line) else { return Vec::new() };
+    entry.message.content.iter()
+        .filter(|b| b.name.as_deref() == Some("Bash"))
+        .filter_map(|b| b.input.as_ref()?.command.as_deref())
+        .flat_map(|command| shell::file_ops(command, cwd))
+        .collect()
+}
+
+/// Whether a transcript line runs a Bash command that deletes or renames
+/// files, which may change the diff (see `cache::load_diff`).
+pub fn has_file_ops(line: &str) -> bool {
+    line.contains("\"Bash\"") && !bash_file_ops(line, Some(Path::new("/"))).is_empty()
+}
+
+/// How far a transcript has been read, and the tip of its conversation.
+#[derive(Serialize, Deserialize, Default)]
+struct TranscriptState {
+    /// Byte offset just past the last complete line read.
+    offset: u64,
+    last_uuid: Option<String>,
+}
 
 /// Edit records from a single transcript, in file order, restricted to the
 /// active conversation branch.
 fn parse_records(transcript_path: &str) -> Vec<Record> {
-    let file = match File::open(transcript_path) {
-        Ok(f) => f,
-        Err(_) => return Vec::new(),
-    };
+    scan_transcript(Path::new(transcript_path), &mut TranscriptState::default()).unwrap_or_default()
+}
+
+/// Reads the complete lines appended since `state.offset` and returns their
+/// edit records on the active branch. None when the transcript was truncated
+/// or a new entry doesn't continue from the one before it, as in
+/// `cache::skip_harmless_lines`: it may be a rewind abandoning records read
+/// earlier, and only a re-read from the start, with the whole tree, tells.
+fn scan_transcript(path: &Path, state: &mut TranscriptState) -> Option<Vec<Record>> {
+    let Ok(mut file) = File::open(path) else { return Some(Vec::new()) };
+    if file.metadata().ok()?.len() < state.offset {
+        return None;
+    }
+    file.seek(SeekFrom::Start(state.offset)).ok()?;
 
     let exclude_dirs: Vec<String> = std::env::var("HOME")
         .map(|h| vec![format!("{}/.claude/plans/", h), format!("{}/.claude/projects/", h)])
         .unwrap_or_default();
 
-    let reader = BufReader::new(file);
+    let mut reader = BufReader::new(file);
+    // Appending to a known tip: the new entries must form a straight line
+    // from it, so every record read is on the active branch.
+    let appending = state.last_uuid.is_some();
     let mut parents: HashMap<String, Option<String>> = HashMap::new();
-    let mut last_uuid: Option<String> = None;
-    let mut records: Vec<(String, Option<String>, Option<String>, ToolUseResult)> = Vec::new();
+    let mut records: Vec<(Option<String>, Option<String>, Change)> = Vec::new();
+    let mut line = String::new();
 
-    for line in reader.lines().map_while(Result::ok) {
-        if !line.contains("\"uuid\"") && !line.contains("\"toolUseResult\"") {
+    loop {
+        line.clear();
+        let Ok(n) = reader.read_line(&mut line) else { break };
+        if n == 0 {
+            break;
+        }
+        // A line still being written: leave it for the next read unless it's
+        // already whole (a final line without a newline).
+        if !line.ends_with('\n') && serde_json::from_str::<serde_json::Value>(&line).is_err() {
+            break;
+        }
+        state.offset += n as u64;
+        if !line.contains("\"uuid\"") && !line.contains("\"toolUseResult\"") && !line.contains("\"Bash\"") {
             continue;
         }
         let Ok(entry) = serde_json::from_str::<LineEntry>(&line) else { continue };
         if let Some(u) = &entry.uuid {
+            if appending && entry.parent_uuid != state.last_uuid {
+                return None;
+            }
             parents.insert(u.clone(), entry.parent_uuid.clone());
-            last_uuid = Some(u.clone());
+            state.last_uuid = Some(u.clone());
+        }
+        if line.contains("\"Bash\"") {
+            for op in bash_file_ops(&line, entry.cwd.as_deref().map(Path::new)) {
+                records.push((entry.uuid.clone(), entry.timestamp.clone(), Change::Shell(op)));
+            }
         }
         if let Some(v) = entry.tool_use_result
             && v.is_object()
@@ -79,17 +200,21 @@ fn parse_records(transcript_path: &str) -> Vec<Record> {
                     if exclude_dirs.iter().any(|d| file_path.starts_with(d)) {
                         continue;
                     }
-                    records.push((file_path, entry.uuid.clone(), entry.timestamp.clone(), result));
+                    records.push((entry.uuid.clone(), entry.timestamp.clone(), Change::Edit(file_path, Box::new(result))));
                 }
     }
 
+    if appending {
+        return Some(records.into_iter().map(|(_, ts, change)| (ts, change)).collect());
+    }
+
     // The transcript is a tree: rewinding a session abandons a branch whose
     // edits were rolled back. The active path is the parent chain of the last
     // uuid-bearing entry. An entry is abandoned only if it hangs off the
     // active path without being on it; entries in disconnected components
     // (sidechains, pre-compact history) still count.
     let mut active: HashSet<String> = HashSet::new();
-    let mut cur = last_uuid;
+    let mut cur = state.last_uuid.clone();
     while let Some(u) = cur {
         if !active.insert(u.clone()) {
             break;
@@ -99,15 +224,15 @@ fn parse_records(transcript_path: &str) -> Vec<Record> {
 
     let mut memo: HashMap<String, bool> = HashMap::new();
     let mut surviving: Vec<Record> = Vec::new();
-    for (file_path, uuid, ts, result) in records {
+    for (uuid, ts, change) in records {
         if let Some(u) = &uuid
             && is_abandoned(u, &parents, &active, &mut memo) {
                 continue;
             }
-        surviving.push((file_path, ts, result));
+        surviving.push((ts, change));
     }
 
-    surviving
+    Some(surviving)
 }
 
 /// Subagent transcripts for a session live at `<dir>/<stem>/subagents/*.jsonl`
@@ -121,8 +246,8 @@ fn subagent_transcripts(transcript_path: &str) -> Vec<PathBuf> {
     fsutil::jsonl_files(&parent.join(stem).join(fsutil::SUBAGENTS_DIR))
 }
 
-/// Combined byte size of the subagent transcripts — the cache freshness signal
-/// (a new subagent file or a grown one changes this; see `cache::load`).
+/// Combined byte size of the transcripts — the cache freshness signal (a new
+/// transcript or a grown one changes this; see `cache::load_diff`).
 fn subagent_signature(paths: &[PathBuf]) -> u64 {
     paths.iter().map(|p| fs::metadata(p).map(|m| m.len()).unwrap_or(0)).sum()
 }
@@ -134,31 +259,96 @@ fn ts_key(ts: &Option<String>) -> &str {
     ts.as_deref().map(|s| s.get(0..19).unwrap_or(s)).unwrap_or("")
 }
 
+/// Each file's folded history, in first-touched order, keyed by the path
+/// it has now.
+#[derive(Serialize, Deserialize, Default)]
+struct SessionFiles {
+    order: Vec<String>,
+    files: HashMap<String, FileState>,
+}
+
+impl SessionFiles {
+    fn apply(&mut self, origin: &str, (ts, change): Record) {
+        match change {
+            Change::Edit(file_path, result) => {
+                if !self.files.contains_key(&file_path) {
+                    self.order.push(file_path.clone());
+                }
+                self.files.entry(file_path.clone()).or_default().fold(&file_path, &result, &ts, origin);
+            }
+            Change::Shell(FileOp::Delete { path, recursive }) => {
+                let glob = path.contains(['*', '?', '[']);
+                let dir = format!("{}/", path.trim_end_matches('/'));
+                for (file_path, file) in self.files.iter_mut() {
+                    let hit = *file_path == path
+                        || (recursive && file_path.starts_with(&dir))
+                        || (glob && fsutil::glob_match(&path, file_path));
+                    if hit {
+                        file.delete();
+                    }
+                }
+            }
+            Change::Shell(FileOp::Rename { from, to }) => {
+                let moves: Vec<(String, String)> = self.order.iter()
+                    .filter_map(|p| rename_target(p, &from, &to).map(|t| (p.clone(), t)))
+                    .collect();
+                for (old, new) in moves {
+                    let Some(mut file) = self.files.remove(&old) else { continue };
+                    file.renamed_from.get_or_insert(old.clone());
+                    // Whatever the destination held before was overwritten.
+                    self.order.retain(|p| *p != new);
+                    if let Some(slot) = self.order.iter_mut().find(|p| **p == old) {
+                        *slot = new.clone();
+                    }
+                    self.files.insert(new, file);
+                }
+            }
+        }
+    }
+}
+
+/// Where `mv from to` puts the tracked file `path`, if it moves it. A `to`
+/// ending in `/` (or several sources) is a directory to move into; otherwise
+/// the disk tells whether it's a directory that received `from` or its new
+/// name.
+fn rename_target(path: &str, from: &str, to: &str) -> Option<String> {
+    let rest = if path == from { "" } else { path.strip_prefix(from)?.strip_prefix('/')? };
+    let name = Path::new(from).file_name()?.to_string_lossy();
+    let into = match to.strip_suffix('/') {
+        Some(dir) => Some(format!("{}/{}", dir, name)),
+        None => {
+            let inside = format!("{}/{}", to, name);
+            Path::new(&inside).exists().then_some(inside)
+        }
+    };
+    let base = into.unwrap_or_else(|| to.to_string());
+    Some(if rest.is_empty() { base } else { format!("{}/{}", base, rest) })
+}
+
 /// Merges the main transcript's records with every subagent transcript's,
 /// ordered chronologically so a file edited by both reconstructs correctly.
-fn collect_records(transcript_path: &str, subagents: &[PathBuf]) -> (Vec<String>, HashMap<String, Vec<ToolUseResult>>) {
-    let mut records = parse_records(transcript_path);
+fn collect_records(transcript_path: &str, subagents: &[PathBuf]) -> SessionFiles {
+    let tagged = |origin: &str| -> Vec<(String, Record)> {
+        parse_records(origin).into_iter().map(|r| (origin.to_string(), r)).collect()
+    };
+    let mut records = tagged(transcript_path);
     if !subagents.is_empty() {
         for sub in subagents {
-            records.extend(parse_records(&sub.to_string_lossy()));
+            records.extend(tagged(&sub.to_string_lossy()));
         }
         // Merge the transcripts chronologically. Stable, so records with equal
         // (or missing) timestamps keep their original per-file order. Skipped
         // entirely when there are no subagents: the single-transcript path then
         // keeps its append-only file order, the reliable signal it always used,
         // without depending on every record carrying a timestamp.
-        records.sort_by(|a, b| ts_key(&a.1).cmp(ts_key(&b.1)));
+        records.sort_by(|a, b| ts_key(&a.1.0).cmp(ts_key(&b.1.0)));
     }
 
-    let mut order: Vec<String> = Vec::new();
-    let mut by_file: HashMap<String, Vec<ToolUseResult>> = HashMap::new();
-    for (file_path, _ts, result) in records {
-        if !by_file.contains_key(&file_path) {
-            order.push(file_path.clone());
-        }
-        by_file.entry(file_path).or_default().push(result);
+    let mut session = SessionFiles::default();
+    for (origin, record) in records {
+        session.apply(&origin, record);
     }
-    (order, by_file)
+    session
 }
 
 fn is_abandoned(
@@ -198,58 +388,963 @@ fn is_abandoned(
     abandoned
 }
 
-pub fn calculate_net_diff(transcript_path: &str) -> (usize, usize) {
-    let cache_path = cache::get_cache_path(transcript_path);
+/// Which files count toward the session diff, per the `diff` config section.
+pub struct DiffFilter<'a> {
+    cfg: &'a DiffConfig,
+    /// Root that `repo_only` and relative patterns are resolved against.
+    root: Option<PathBuf>,
+    /// The repository containing the session's cwd.
+    repo: Option<PathBuf>,
+}
+
+impl<'a> DiffFilter<'a> {
+    pub fn new(cfg: &'a DiffConfig, cwd: &str) -> DiffFilter<'a> {
+        let active = cfg.repo_only || !cfg.include.is_empty() || !cfg.exclude.is_empty();
+        let repo = git::get_repo_root(cwd);
+        let root = active.then(|| repo.clone().unwrap_or_else(|| PathBuf::from(cwd)));
+        DiffFilter { cfg, root, repo }
+    }
+
+    /// The file lies outside the session's repository; never true outside one.
+    fn outside_repo(&self, file_path: &str) -> bool {
+        self.repo.as_ref().is_some_and(|r| !Path::new(file_path).starts_with(r))
+    }
+
+    pub fn counts(&self, file_path: &str) -> bool {
+        let path = Path::new(file_path);
+        if self.cfg.repo_only && !self.root.as_ref().is_some_and(|r| path.starts_with(r)) {
+            return false;
+        }
+        if !self.cfg.include.is_empty() && !self.cfg.include.iter().any(|p| self.matches(p, path)) {
+            return false;
+        }
+        !self.cfg.exclude.iter().any(|p| self.matches(p, path))
+    }
+
+    fn matches(&self, pattern: &str, path: &Path) -> bool {
+        if !pattern.contains('/') {
+            return path.file_name().and_then(|n| n.to_str()).is_some_and(|n| fsutil::glob_match(pattern, n));
+        }
+        if pattern.starts_with('/') || pattern.starts_with("~/") {
+            return fsutil::glob_match(&fsutil::expand_tilde(pattern).to_string_lossy(), &path.to_string_lossy());
+        }
+        self.root.as_ref()
+            .and_then(|r| path.strip_prefix(r).ok())
+            .is_some_and(|rel| fsutil::glob_match(pattern, &rel.to_string_lossy()))
+    }
+
+    /// Identifies the filter in the cache, so a config edit or a session that
+    /// moved to another repo recomputes the counts.
+    fn signature(&self) -> String {
+        let mut sig = match &self.root {
+            Some(root) => format!("{}|{}|{}|{}", root.display(), self.cfg.repo_only, self.cfg.include.join(","), self.cfg.exclude.join(",")),
+            None => String::new(),
+        };
+        if self.cfg.on_disk {
+            sig.push_str("|on_disk");
+        }
+        if let Some(repo) = &self.repo {
+            sig.push_str(&format!("|repo={}", repo.display()));
+        }
+        sig
+    }
+}
+
+/// The session diff totals the status line shows.
+#[derive(Default)]
+pub struct NetDiff {
+    pub added: usize,
+    pub removed: usize,
+    /// Churn in files the filter left out.
+    pub excluded: usize,
+    /// Files whose content on disk no longer matches what the session left.
+    pub drifted: usize,
+    /// The main transcript's share, then each subagent's that edited a
+    /// counted file.
+    pub agents: Vec<AgentDiff>,
+    pub files: FileCounts,
+}
+
+/// How many files the session changed, for the `files*` fields.
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct FileCounts {
+    /// Counted files with a net change, deletions included.
+    pub changed: usize,
+    /// Of those, files the session created.
+    pub created: usize,
+    /// Of those, files that existed before the session and were deleted.
+    pub deleted: usize,
+    /// Files touched at all outside the session's repository, counted or not.
+    pub outside_repo: usize,
+}
+
+/// One transcript's share of the session diff (see `FileState::attribute`).
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct AgentDiff {
+    pub transcript: String,
+    pub added: usize,
+    pub removed: usize,
+    /// Counted files the transcript edited.
+    pub files: usize,
+}
+
+/// Orders the shares main transcript first (present even when it edited
+/// nothing), then subagents by path.
+fn agent_list(transcript_path: &str, mut agents: BTreeMap<String, AgentDiff>) -> Vec<AgentDiff> {
+    let main = agents.remove(transcript_path)
+        .unwrap_or_else(|| AgentDiff { transcript: transcript_path.to_string(), ..Default::default() });
+    std::iter::once(main).chain(agents.into_values()).collect()
+}
+
+/// Each transcript's share of the session diff, for `--diff-agents`.
+pub fn agent_diffs(transcript_path: &str, filter: &DiffFilter) -> Vec<AgentDiff> {
+    let subagents = subagent_transcripts(transcript_path);
+    let mut session = collect_records(transcript_path, &subagents);
+
+    let mut agents = BTreeMap::new();
+    for (file_path, file) in session.files.iter_mut() {
+        if !file.counted(file_path) || !filter.counts(file_path) {
+            continue;
+        }
+        let (counts, _) = file.net_counts(file_path, filter.cfg.on_disk);
+        file.attribute(counts, &mut agents);
+    }
+    agent_list(transcript_path, agents)
+}
+
+/// Net (added, removed) lines for the session, plus the churn in files the
+/// filter left out and how many files drifted since the session edited them.
+pub fn calculate_net_diff(transcript_path: &str, filter: &DiffFilter) -> NetDiff {
     let subagents = subagent_transcripts(transcript_path);
     let subagent_sig = subagent_signature(&subagents);
+    let filter_sig = filter.signature();
 
     // Try cache first
-    if let Some(c) = cache::load(&cache_path, transcript_path, subagent_sig) {
-        return (c.added, c.removed);
-    }
+    if let Some(c) = cache::load_diff(transcript_path, subagent_sig)
+        && c.filter == filter_sig {
+            return NetDiff {
+                added: c.added,
+                removed: c.removed,
+                excluded: c.excluded,
+                drifted: c.drifted,
+                agents: c.agents,
+                files: c.file_counts,
+            };
+        }
 
-    // Cache miss: parse and compute
-    let (order, mut by_file) = collect_records(transcript_path, &subagents);
+    // Cache miss: fold in whatever was appended since the last render
+    let mut state: DiffState = cache::load_diff_state(transcript_path).unwrap_or_default();
+    if !state.update(transcript_path, &subagents) {
+        state = DiffState::default();
+        state.update(transcript_path, &subagents);
+    }
 
-    let mut added = 0;
-    let mut removed = 0;
-    let mut files = Vec::new();
+    let mut net = NetDiff::default();
+    let mut agents = BTreeMap::new();
+    let mut files: Vec<String> = Vec::new();
+    let mut file_mtimes: Vec<u128> = Vec::new();
 
-    for file_path in order {
-        let Some(records) = by_file.remove(&file_path) else { continue };
-        if !std::path::Path::new(&file_path).exists() {
+    for (file_path, file) in state.session.files.iter_mut() {
+        if !file.counted(file_path) {
             continue;
         }
-        files.push(file_path);
-        let (a, r) = diff_for_file(&records);
-        added += a;
-        removed += r;
+        let ((a, r), drifted) = file.net_counts(file_path, filter.cfg.on_disk);
+        if filter.counts(file_path) {
+            net.added += a;
+            net.removed += r;
+            net.drifted += drifted as usize;
+            file.attribute((a, r), &mut agents);
+            // A pre-existing file deleted is a change even when its prior
+            // content (and so its removed lines) isn't known.
+            let deleted = file.deleted && !file.created;
+            if a + r > 0 || deleted {
+                net.files.changed += 1;
+                if deleted {
+                    net.files.deleted += 1;
+                } else if file.created {
+                    net.files.created += 1;
+                }
+            }
+        } else {
+            net.excluded += a + r;
+        }
+        net.files.outside_repo += filter.outside_repo(file_path) as usize;
+        // Deleted files are gone from disk; there's nothing to watch.
+        if let Some(mtime) = fsutil::mtime_ms(Path::new(file_path)) {
+            files.push(file_path.clone());
+            file_mtimes.push(mtime);
+        }
     }
+    net.agents = agent_list(transcript_path, agents);
+    cache::save_diff_state(transcript_path, &state);
 
-    // Save cache, preserving existing claude_pid
-    let existing_pid = cache::load_raw(&cache_path).and_then(|c| c.claude_pid);
-    cache::save(&cache_path, &cache::Cache {
-        byte_offset: cache::get_file_size(transcript_path),
-        added,
-        removed,
+    let main = state.transcripts.get(transcript_path);
+    let diff = cache::DiffCache {
+        byte_offset: main.map_or(0, |t| t.offset),
+        last_uuid: main.and_then(|t| t.last_uuid.clone()),
+        added: net.added,
+        removed: net.removed,
         files,
+        file_mtimes,
         subagent_sig,
-        claude_pid: existing_pid,
+        excluded: net.excluded,
+        drifted: net.drifted,
+        agents: net.agents.clone(),
+        file_counts: net.files.clone(),
+        filter: filter_sig,
+        project: None,
+    };
+    // `diff-today`'s totals share the section but are computed separately.
+    cache::update(transcript_path, |c| c.diff = cache::DiffCache { project: c.diff.project.take(), ..diff });
+
+    net
+}
+
+/// `diff.mode` `git-head` and `git-session-base`: the working tree against
+/// `base` (HEAD when None) as git sees it, instead of the transcript. Nothing
+/// is attributed to agents or checked for drift, and of the file counts only
+/// `changed` is known.
+pub fn calculate_git_diff(cwd: &str, transcript_path: &str, filter: &DiffFilter, base: Option<&str>) -> NetDiff {
+    let mut net = NetDiff::default();
+    for (path, added, removed) in git::get_git_diff(cwd, transcript_path, base).unwrap_or_default() {
+        if filter.counts(&path) {
+            net.added += added;
+            net.removed += removed;
+            net.files.changed += (added + removed > 0) as usize;
+        } else {
+            net.excluded += added + removed;
+        }
+    }
+    net
+}
+
+/// Net change across every session run in one repository since a cutoff,
+/// for `diff-today` and `--diff-summary`.
+#[derive(Default)]
+pub struct ProjectDiff {
+    /// The repository (or directory) the sessions ran in.
+    pub root: PathBuf,
+    pub added: usize,
+    pub removed: usize,
+    /// Sessions with an edit or Bash file operation since the cutoff.
+    pub sessions: usize,
+    /// Counted files with a net change as (path, added, removed), most churn
+    /// first.
+    pub files: Vec<(String, usize, usize)>,
+}
+
+/// Where a session ran: the repository containing `cwd`, or `cwd` itself
+/// outside one.
+fn project_root(cwd: &str) -> PathBuf {
+    git::get_repo_root(cwd).unwrap_or_else(|| PathBuf::from(cwd))
+}
+
+/// The cwd a session started in, from the first entries that record one.
+pub fn transcript_cwd(path: &Path) -> Option<String> {
+    let reader = BufReader::new(File::open(path).ok()?);
+    reader.lines()
+        .take(50)
+        .map_while(Result::ok)
+        .filter(|l| l.contains("\"cwd\""))
+        .find_map(|l| serde_json::from_str::<LineEntry>(&l).ok()?.cwd)
+}
+
+/// Project root of each recently written main transcript, by path, so
+/// sessions are only opened once to find where they ran. Shared by every
+/// project (see `cache::load_project_roots`).
+type TranscriptRoots = HashMap<String, String>;
+
+/// Main transcripts of the sessions that ran in `root`, each with its
+/// subagent transcripts, skipping sessions none of whose transcripts were
+/// written to since `since_ms`. `roots` gains the sessions not seen before
+/// and drops the ones no longer recent.
+fn project_sessions(root: &Path, since_ms: u128, roots: &mut TranscriptRoots) -> Vec<(PathBuf, Vec<PathBuf>)> {
+    let mut cwd_roots: HashMap<String, PathBuf> = HashMap::new();
+    let mut recent: HashSet<String> = HashSet::new();
+    let mut sessions: Vec<(PathBuf, Vec<PathBuf>)> = usage::list_transcripts()
+        .into_iter()
+        .filter(|p| p.parent().and_then(Path::file_name) != Some(OsStr::new(fsutil::SUBAGENTS_DIR)))
+        .filter_map(|main| {
+            let subagents = subagent_transcripts(&main.to_string_lossy());
+            let is_recent = std::iter::once(&main)
+                .chain(&subagents)
+                .any(|t| fsutil::mtime_ms(t).is_some_and(|m| m >= since_ms));
+            if !is_recent {
+                return None;
+            }
+            let key = main.to_string_lossy().into_owned();
+            recent.insert(key.clone());
+            if !roots.contains_key(&key) {
+                // Not remembered until known: a new session may not have
+                // recorded its cwd yet.
+                let cwd = transcript_cwd(&main)?;
+                let session_root = cwd_roots.entry(cwd.clone()).or_insert_with(|| project_root(&cwd));
+                roots.insert(key.clone(), session_root.to_string_lossy().into_owned());
+            }
+            (Path::new(&roots[&key]) == root).then_some((main, subagents))
+        })
+        .collect();
+    roots.retain(|k, _| recent.contains(k));
+    sessions.sort();
+    sessions
+}
+
+/// A transcript's edit records as read so far, kept between computations of
+/// the project-wide diff so each one only reads what was appended.
+#[derive(Serialize, Deserialize, Default)]
+struct TranscriptRecords {
+    state: TranscriptState,
+    records: Vec<Record>,
+}
+
+/// The records of every transcript in one project's recent sessions, by
+/// path, persisted per project root (see `cache::load_project_state`).
+#[derive(Serialize, Deserialize, Default)]
+pub struct ProjectState {
+    transcripts: HashMap<String, TranscriptRecords>,
+}
+
+impl ProjectState {
+    /// Reads what was appended to `path` since the last call and returns all
+    /// of its records. A rewind or truncation re-reads it from the start.
+    fn records(&mut self, path: &Path) -> &[Record] {
+        let t = self.transcripts.entry(path.to_string_lossy().into_owned()).or_default();
+        match scan_transcript(path, &mut t.state) {
+            Some(new) => t.records.extend(new),
+            None => {
+                *t = TranscriptRecords::default();
+                t.records = scan_transcript(path, &mut t.state).unwrap_or_default();
+            }
+        }
+        &t.records
+    }
+
+    /// Forgets transcripts outside `sessions`, so the state only holds the
+    /// project's recent ones.
+    fn retain(&mut self, sessions: &[(PathBuf, Vec<PathBuf>)]) {
+        let keep: HashSet<String> = sessions.iter()
+            .flat_map(|(main, subs)| std::iter::once(main).chain(subs))
+            .map(|p| p.to_string_lossy().into_owned())
+            .collect();
+        self.transcripts.retain(|k, _| keep.contains(k));
+    }
+}
+
+/// Merges the records every session in `sessions` made since `since` into
+/// one history per file, ordered by timestamp, so an edit one session made
+/// and another undid nets to zero. Records without a timestamp can't be
+/// placed against the cutoff and are left out. Transcripts are read through
+/// `state`, which keeps what was read for next time.
+fn merge_sessions(sessions: &[(PathBuf, Vec<PathBuf>)], since: &str, filter: &DiffFilter, state: &mut ProjectState) -> ProjectDiff {
+    let mut out = ProjectDiff::default();
+    let mut records: Vec<(String, Record)> = Vec::new();
+    for (main, subagents) in sessions {
+        let before = records.len();
+        for path in std::iter::once(main).chain(subagents) {
+            let origin = path.to_string_lossy().into_owned();
+            records.extend(state.records(path)
+                .iter()
+                .filter(|(ts, _)| ts.is_some() && ts_key(ts) >= since)
+                .map(|r| (origin.clone(), r.clone())));
+        }
+        out.sessions += (records.len() > before) as usize;
+    }
+    state.retain(sessions);
+    records.sort_by(|a, b| ts_key(&a.1.0).cmp(ts_key(&b.1.0)));
+    let mut merged = SessionFiles::default();
+    for (origin, record) in records {
+        merged.apply(&origin, record);
+    }
+
+    let SessionFiles { order, mut files } = merged;
+    for file_path in order {
+        let Some(file) = files.get_mut(&file_path) else { continue };
+        if !file.counted(&file_path) || !filter.counts(&file_path) {
+            continue;
+        }
+        let ((added, removed), _) = file.net_counts(&file_path, filter.cfg.on_disk);
+        if added + removed > 0 || (file.deleted && !file.created) {
+            out.added += added;
+            out.removed += removed;
+            out.files.push((file_path, added, removed));
+        }
+    }
+    // Stable: equal churn keeps first-touched order.
+    out.files.sort_by_key(|(_, a, r)| std::cmp::Reverse(a + r));
+    out
+}
+
+/// The project's recent sessions, with the transcript roots cache updated.
+fn load_sessions(root: &Path, since_ms: u128) -> Vec<(PathBuf, Vec<PathBuf>)> {
+    let mut roots: TranscriptRoots = cache::load_project_roots().unwrap_or_default();
+    let before = roots.clone();
+    let sessions = project_sessions(root, since_ms, &mut roots);
+    if roots != before {
+        cache::save_project_roots(&roots);
+    }
+    sessions
+}
+
+/// Merges `sessions` through the project's persisted `ProjectState`.
+fn merge_project(root: &Path, sessions: &[(PathBuf, Vec<PathBuf>)], since: &str, filter: &DiffFilter) -> ProjectDiff {
+    let mut state: ProjectState = cache::load_project_state(root).unwrap_or_default();
+    let diff = merge_sessions(sessions, since, filter, &mut state);
+    cache::save_project_state(root, &state);
+    diff
+}
+
+/// Net change since `since` (a UTC timestamp key, see `usage::period_start`,
+/// with `since_ms` the same instant) across every session that ran in the
+/// repository containing `cwd`, for `--diff-summary`.
+pub fn project_diff(cwd: &str, since: &str, since_ms: u128, filter: &DiffFilter) -> ProjectDiff {
+    let root = project_root(cwd);
+    let diff = merge_project(&root, &load_sessions(&root, since_ms), since, filter);
+    ProjectDiff { root, ..diff }
+}
+
+/// `project_diff`'s totals for the `diff-today` field, cached in the session
+/// cache until the cutoff moves, the filter changes or one of the project's
+/// transcripts grows. Then only what the transcripts gained is read.
+pub fn project_totals(cwd: &str, transcript_path: &str, since: &str, since_ms: u128, filter: &DiffFilter) -> (usize, usize) {
+    let root = project_root(cwd);
+    let sessions = load_sessions(&root, since_ms);
+    let all: Vec<PathBuf> = sessions.iter().flat_map(|(main, subs)| std::iter::once(main).chain(subs)).cloned().collect();
+    let sig = subagent_signature(&all);
+    let filter_sig = format!("{}|{}", root.display(), filter.signature());
+
+    let cached = cache::load_raw(transcript_path);
+    if let Some(pd) = cached.as_ref().and_then(|c| c.diff.project.as_ref())
+        && pd.since == since
+        && pd.filter == filter_sig
+        && pd.sig == sig {
+            return (pd.added, pd.removed);
+        }
+
+    let diff = merge_project(&root, &sessions, since, filter);
+    cache::update(transcript_path, |c| {
+        c.diff.project = Some(cache::ProjectDiffCache {
+            since: since.to_string(),
+            filter: filter_sig,
+            sig,
+            added: diff.added,
+            removed: diff.removed,
+        });
     });
+    (diff.added, diff.removed)
+}
 
-    (added, removed)
+/// The session's transcripts read so far and every file's folded history,
+/// persisted between renders so each one only reads what was appended.
+#[derive(Serialize, Deserialize, Default)]
+pub struct DiffState {
+    transcripts: HashMap<String, TranscriptState>,
+    session: SessionFiles,
+    /// Records have been merged across transcripts by timestamp.
+    merged: bool,
+}
+
+impl DiffState {
+    /// Folds in the records appended to each transcript since the last call.
+    /// False when that can't reproduce a full re-read's result — a rewind, a
+    /// truncated transcript, or records arriving out of timestamp order — and
+    /// the state must be rebuilt from scratch.
+    fn update(&mut self, transcript_path: &str, subagents: &[PathBuf]) -> bool {
+        let merge = !subagents.is_empty();
+        // The first subagent switches ordering from file order to timestamps,
+        // which may reorder what was already folded.
+        if merge && !self.merged && !self.session.order.is_empty() {
+            return false;
+        }
+        self.merged |= merge;
+
+        let mut batch: Vec<(String, Record)> = Vec::new();
+        for path in std::iter::once(PathBuf::from(transcript_path)).chain(subagents.iter().cloned()) {
+            let origin = path.to_string_lossy().into_owned();
+            let t = self.transcripts.entry(origin.clone()).or_default();
+            match scan_transcript(&path, t) {
+                Some(records) => batch.extend(records.into_iter().map(|r| (origin.clone(), r))),
+                None => return false,
+            }
+        }
+        if merge {
+            batch.sort_by(|a, b| ts_key(&a.1.0).cmp(ts_key(&b.1.0)));
+        }
+        for (origin, record) in batch {
+            if merge
+                && let (ts, Change::Edit(file_path, _)) = &record
+                && self.session.files.get(file_path).is_some_and(|f| ts_key(ts) < f.last_ts.as_str()) {
+                    return false;
+                }
+            self.session.apply(&origin, record);
+        }
+        true
+    }
+}
+
+/// One file's share of the session diff, for `--diff-files`.
+pub struct FileDiff {
+    pub path: String,
+    pub added: usize,
+    pub removed: usize,
+    /// The session's first record for the file created it.
+    pub created: bool,
+    /// Only subagents edited the file, never the main agent.
+    pub subagent_only: bool,
+    /// Left out of the `diff` total by the diff filter.
+    pub excluded: bool,
+    /// Changed on disk since the session's last edit to it.
+    pub drifted: bool,
+    /// A Bash `rm` in the session deleted it.
+    pub deleted: bool,
+    /// Its path before a Bash `mv` in the session.
+    pub renamed_from: Option<String>,
+}
+
+/// Per-file breakdown of `calculate_net_diff`, most churn first. Files that
+/// no longer exist are left out, as they are from the totals.
+pub fn file_diffs(transcript_path: &str, filter: &DiffFilter) -> Vec<FileDiff> {
+    let subagents = subagent_transcripts(transcript_path);
+    let SessionFiles { order, mut files } = collect_records(transcript_path, &subagents);
+
+    let mut out = Vec::new();
+    for file_path in order {
+        let Some(mut file) = files.remove(&file_path) else { continue };
+        if !file.counted(&file_path) {
+            continue;
+        }
+        let ((added, removed), drifted) = file.net_counts(&file_path, filter.cfg.on_disk);
+        out.push(FileDiff {
+            created: file.created,
+            subagent_only: !file.origins.contains_key(transcript_path),
+            excluded: !filter.counts(&file_path),
+            drifted,
+            deleted: file.deleted,
+            renamed_from: file.renamed_from.take(),
+            path: file_path,
+            added,
+            removed,
+        });
+    }
+    // Stable: equal churn keeps first-touched order.
+    out.sort_by_key(|f| std::cmp::Reverse(f.added + f.removed));
+    out
+}
+
+/// The session's net change to every file it touched, as a unified diff.
+/// Paths under `root` (the session's repository) get `a/`/`b/` prefixes relative to it; files elsewhere
+/// keep their absolute paths. Created files diff from `/dev/null`, deleted
+/// ones to it, and renamed ones from their old path.
+pub fn export_patch(transcript_path: &str, root: Option<&Path>) -> String {
+    let subagents = subagent_transcripts(transcript_path);
+    let SessionFiles { order, mut files } = collect_records(transcript_path, &subagents);
+
+    let mut out = String::new();
+    for file_path in order {
+        let Some(file) = files.remove(&file_path) else { continue };
+        let Some((before, after)) = file.states(&file_path) else {
+            eprintln!("statusline: can't reconstruct {}, skipped", file_path);
+            continue;
+        };
+        if before == after {
+            continue;
+        }
+        let name = |path: &str, prefix: &str| match root.and_then(|r| Path::new(path).strip_prefix(r).ok()) {
+            Some(rel) => format!("{}/{}", prefix, rel.display()),
+            None => path.to_string(),
+        };
+        let old = if file.created { "/dev/null".to_string() } else { name(file.renamed_from.as_deref().unwrap_or(&file_path), "a") };
+        let new = if file.deleted { "/dev/null".to_string() } else { name(&file_path, "b") };
+        out.push_str(&TextDiff::from_lines(&before, &after).unified_diff().header(&old, &new).to_string());
+    }
+    out
+}
+
+/// One file's session history, folded record by record so records appended
+/// later never require revisiting earlier ones. Keeps the latest full
+/// snapshot plus every structuredPatch (enough to rebuild the states either
+/// side of it), the oldString/newString replay for records without patches,
+/// and for notebooks the first and last snapshots.
+#[derive(Serialize, Deserialize, Default)]
+struct FileState {
+    records: usize,
+    patches: Vec<Vec<Hunk>>,
+    /// Some record came without a structuredPatch.
+    unpatched: bool,
+    anchor: Option<Anchor>,
+    /// The first record created the file (see `is_created`).
+    created: bool,
+    /// The first record is a creating Write with an empty patch.
+    creation_write: bool,
+    legacy: LegacyState,
+    notebook: Option<NotebookState>,
+    /// Timestamp key of the last record folded in.
+    last_ts: String,
+    /// (added, removed) as of the last record; cleared by each fold.
+    counts: Option<(usize, usize)>,
+    /// Lines each transcript's own records added and removed, by transcript
+    /// path.
+    origins: BTreeMap<String, (usize, usize)>,
+    /// A Bash `rm` removed the file after its last record.
+    deleted: bool,
+    /// The path the file had before a Bash `mv` renamed it.
+    renamed_from: Option<String>,
+    /// Hash of the content the last record left, as `drift` compares it, so
+    /// an unchanged file is recognized without reconstructing it.
+    after_hash: Option<u64>,
+    /// What `drift` last found, valid while the file's mtime and size hold.
+    drift_check: Option<DriftCheck>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+struct DriftCheck {
+    mtime_ms: u128,
+    size: u64,
+    drift: Option<(usize, usize)>,
+}
+
+/// The latest full snapshot of the file: a non-empty originalFile holds the
+/// content just before its record, a Write's content the content just after.
+#[derive(Serialize, Deserialize)]
+struct Anchor {
+    record: usize,
+    after: bool,
+    lines: Vec<String>,
+}
+
+/// Whole-file (original, final) from Writes, plus (original, current)
+/// snippet pairs for edit chains that didn't land in written content.
+#[derive(Serialize, Deserialize, Default)]
+struct LegacyState {
+    original: Option<String>,
+    current: Option<String>,
+    chains: Vec<(String, String)>,
+}
+
+/// Raw notebook JSON before the first record and after the last, when the
+/// records carry it, and the lines of cells inserted without snapshots.
+#[derive(Serialize, Deserialize, Default)]
+struct NotebookState {
+    edited: bool,
+    before: Option<String>,
+    after: Option<String>,
+    inserted: usize,
 }
 
-fn diff_for_file(records: &[ToolUseResult]) -> (usize, usize) {
-    // structuredPatch is authoritative: originalFile is sometimes empty or
-    // missing even when the file had content, which would turn rewrites into
-    // pure additions. Reconstruct before/after states from the patches and
-    // fall back to oldString/newString chaining when that isn't possible.
-    if records.iter().all(|r| r.structured_patch.is_some())
-        && let Some((before, after)) = patch_reconstruct(records) {
+impl FileState {
+    fn fold(&mut self, file_path: &str, r: &ToolUseResult, ts: &Option<String>, origin: &str) {
+        let index = self.records;
+        self.deleted = false;
+        if index == 0 {
+            self.created = is_created(r);
+            self.creation_write = is_creation_write(r);
+        }
+
+        match &r.structured_patch {
+            Some(p) => self.patches.push(p.clone()),
+            None => self.unpatched = true,
+        }
+        if let Some(content) = &r.content {
+            self.anchor = Some(Anchor { record: index, after: true, lines: split_lines(content) });
+        } else if let Some(original) = r.original_file.as_deref().filter(|s| !s.is_empty()) {
+            self.anchor = Some(Anchor { record: index, after: false, lines: split_lines(original) });
+        }
+
+        let legacy = &mut self.legacy;
+        if let Some(content) = &r.content {
+            if legacy.original.is_none() {
+                legacy.original = Some(r.original_file.clone().unwrap_or_default());
+            }
+            legacy.current = Some(content.clone());
+            legacy.chains.clear();
+        } else if let (Some(old_str), Some(new_str)) = (&r.old_string, &r.new_string) {
+            replay_edit(old_str, new_str, &mut legacy.current, &mut legacy.chains);
+        } else if let Some(edits) = &r.edits {
+            for e in edits {
+                replay_edit(&e.old_string, &e.new_string, &mut legacy.current, &mut legacy.chains);
+            }
+        }
+
+        if file_path.ends_with(".ipynb") {
+            let nb = self.notebook.get_or_insert_default();
+            if index == 0 {
+                // A creating Write has no before state: the notebook is new.
+                nb.before = if self.created {
+                    Some(String::new())
+                } else {
+                    r.notebook.original_file.clone().or_else(|| r.original_file.clone().filter(|s| !s.is_empty()))
+                };
+            }
+            nb.after = r.notebook.updated_file.clone().or_else(|| r.content.clone());
+            if r.is_notebook_edit() {
+                nb.edited = true;
+                if r.notebook.edit_mode.as_deref() == Some("insert") {
+                    nb.inserted += r.notebook.new_source.as_deref().map_or(0, |src| src.lines().count());
+                }
+            }
+        }
+
+        let (added, removed) = record_counts(r);
+        let share = self.origins.entry(origin.to_string()).or_default();
+        share.0 += added;
+        share.1 += removed;
+
+        self.records += 1;
+        self.last_ts = ts_key(ts).to_string();
+        self.counts = None;
+        self.after_hash = None;
+        self.drift_check = None;
+    }
+
+    fn delete(&mut self) {
+        self.deleted = true;
+        self.counts = None;
+        self.drift_check = None;
+    }
+
+    /// Whether the file counts toward the diff: it's on disk, or the session
+    /// deleted it. Files that vanished otherwise (another session, a
+    /// checkout) are left out.
+    fn counted(&self, file_path: &str) -> bool {
+        self.deleted || Path::new(file_path).exists()
+    }
+
+    /// The file's (added, removed) and whether it drifted, counted from disk
+    /// when `on_disk` is set and it did.
+    fn net_counts(&mut self, file_path: &str, on_disk: bool) -> ((usize, usize), bool) {
+        let counts = self.counts();
+        match self.drift(file_path) {
+            Some(disk) => (if on_disk { disk } else { counts }, true),
+            None => (counts, false),
+        }
+    }
+
+    /// Adds the file's `counts` to the transcripts that edited it. A file one
+    /// transcript edited alone is its net change; a file several edited is
+    /// split by the lines each one's own records added and removed, since
+    /// interleaved edits have no per-agent net.
+    fn attribute(&self, counts: (usize, usize), agents: &mut BTreeMap<String, AgentDiff>) {
+        let single = self.origins.len() == 1;
+        for (origin, &own) in &self.origins {
+            let (added, removed) = if single { counts } else { own };
+            let share = agents.entry(origin.clone())
+                .or_insert_with(|| AgentDiff { transcript: origin.clone(), ..Default::default() });
+            share.added += added;
+            share.removed += removed;
+            share.files += 1;
+        }
+    }
+
+    fn counts(&mut self) -> (usize, usize) {
+        if let Some(c) = self.counts {
+            return c;
+        }
+        let c = self.compute_counts();
+        self.counts = Some(c);
+        c
+    }
+
+    fn compute_counts(&self) -> (usize, usize) {
+        // A deleted file removes whatever it held before the session; lines
+        // the session added and then deleted never reached the tree. Nothing
+        // can be counted when the prior content isn't known.
+        if self.deleted {
+            let before = self.before().unwrap_or_default();
+            let before = if self.notebook.is_some() { cell_sources(&before).unwrap_or_default() } else { before };
+            return compute_diff(&before, "");
+        }
+        // Notebooks are counted by cell source, not by their JSON: re-serialized
+        // outputs and metadata would otherwise swamp the lines actually edited.
+        // Without snapshots only inserted cells can be counted.
+        if let Some(nb) = self.notebook.as_ref().filter(|nb| nb.edited) {
+            if let (Some(before), Some(after)) = (&nb.before, &nb.after) {
+                return compute_diff(&cell_sources(before).unwrap_or_default(), &cell_sources(after).unwrap_or_default());
+            }
+            return (nb.inserted, 0);
+        }
+        // structuredPatch is authoritative: originalFile is sometimes empty or
+        // missing even when the file had content, which would turn rewrites into
+        // pure additions. Reconstruct before/after states from the patches and
+        // fall back to oldString/newString chaining when that isn't possible.
+        if let Some((before, after)) = self.reconstruct() {
             return compute_diff(&join_normalized(&before), &join_normalized(&after));
         }
-    legacy_diff(records)
+        let mut added = 0;
+        let mut removed = 0;
+        let write = self.legacy.original.as_ref().zip(self.legacy.current.as_ref());
+        for (original, final_content) in self.legacy.chains.iter().map(|(o, c)| (o, c)).chain(write) {
+            let (a, r) = compute_diff(original, final_content);
+            added += a;
+            removed += r;
+        }
+        (added, removed)
+    }
+
+    /// Rebuild the file's content before the first record and after the last
+    /// one from the anchor: earlier patches are un-applied backward, later
+    /// ones applied forward.
+    fn reconstruct(&self) -> Option<(Vec<String>, Vec<String>)> {
+        if self.unpatched {
+            return None;
+        }
+        let anchor = self.anchor.as_ref()?;
+        let split = if anchor.after { anchor.record + 1 } else { anchor.record };
+
+        let mut after = anchor.lines.clone();
+        for p in &self.patches[split..] {
+            after = apply_patch(&after, p, false)?;
+        }
+        // A Write that creates a new file carries content but an empty patch and an
+        // empty/missing originalFile — an empty patch over non-empty content is only
+        // consistent with the file not having existed before. The empty patch holds
+        // no diff to un-apply, so the backward walk would wrongly leave `before`
+        // equal to the created content. The true prior state is empty.
+        if self.creation_write {
+            return Some((Vec::new(), after));
+        }
+        let mut before = anchor.lines.clone();
+        for p in self.patches[..split].iter().rev() {
+            before = apply_patch(&before, p, true)?;
+        }
+        Some((before, after))
+    }
+
+    /// The file's content before the session's first record, when known.
+    fn before(&self) -> Option<String> {
+        if self.created {
+            return Some(String::new());
+        }
+        if let Some(nb) = self.notebook.as_ref().filter(|nb| nb.edited) {
+            return nb.before.clone();
+        }
+        if let Some((before, _)) = self.reconstruct() {
+            return Some(join_lines(&before));
+        }
+        self.legacy.original.clone()
+    }
+
+    /// The (added, removed) lines between the session's starting state and
+    /// the file on disk, when the disk no longer matches the state the
+    /// session left: edited by hand, reformatted, checked out. None when it
+    /// matches, or when the end state isn't known in full (legacy edit
+    /// chains, notebooks without snapshots). Rechecked only once the file's
+    /// mtime or size moves.
+    fn drift(&mut self, file_path: &str) -> Option<(usize, usize)> {
+        if self.deleted {
+            return None;
+        }
+        let meta = fs::metadata(file_path).ok()?;
+        let mtime_ms = meta.modified().ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_millis());
+        let size = meta.len();
+        if let Some(check) = self.drift_check.filter(|c| c.mtime_ms == mtime_ms && c.size == size) {
+            return check.drift;
+        }
+        let drift = self.check_drift(file_path);
+        self.drift_check = Some(DriftCheck { mtime_ms, size, drift });
+        drift
+    }
+
+    fn check_drift(&mut self, file_path: &str) -> Option<(usize, usize)> {
+        let disk = fs::read_to_string(file_path).ok()?;
+        if let Some(nb) = self.notebook.as_ref().filter(|nb| nb.edited) {
+            let after = cell_sources(nb.after.as_deref()?)?;
+            let disk = cell_sources(&disk)?;
+            if after == disk {
+                return None;
+            }
+            return Some(compute_diff(&cell_sources(nb.before.as_deref()?)?, &disk));
+        }
+        let disk = join_normalized(&split_lines(&disk));
+        if self.after_hash == Some(content_hash(&disk)) {
+            return None;
+        }
+        let (before, after) = match self.reconstruct() {
+            Some((before, after)) => (join_normalized(&before), join_normalized(&after)),
+            None => {
+                let legacy = &self.legacy;
+                if !legacy.chains.is_empty() {
+                    return None;
+                }
+                (join_normalized(&split_lines(legacy.original.as_deref()?)), join_normalized(&split_lines(legacy.current.as_deref()?)))
+            }
+        };
+        self.after_hash = Some(content_hash(&after));
+        (disk != after).then(|| compute_diff(&before, &disk))
+    }
+
+    /// The file's full content before the session's first record and after
+    /// its last, for `--export-patch`. Legacy edit chains carry only the
+    /// edited snippets, so those are placed in the file as it is on disk now
+    /// and undone to recover `before`.
+    fn states(&self, file_path: &str) -> Option<(String, String)> {
+        if self.deleted {
+            return Some((self.before()?, String::new()));
+        }
+        if let Some(nb) = self.notebook.as_ref().filter(|nb| nb.edited) {
+            return nb.before.clone().zip(nb.after.clone());
+        }
+        if let Some((before, after)) = self.reconstruct() {
+            return Some((join_lines(&before), join_lines(&after)));
+        }
+        let legacy = &self.legacy;
+        match (&legacy.original, &legacy.current) {
+            (Some(original), Some(current)) if legacy.chains.is_empty() => Some((original.clone(), current.clone())),
+            (None, _) => {
+                let after = fs::read_to_string(file_path).ok()?;
+                let mut before = after.clone();
+                for (original, current) in legacy.chains.iter().rev() {
+                    if !before.contains(current.as_str()) {
+                        return None;
+                    }
+                    before = before.replacen(current.as_str(), original, 1);
+                }
+                Some((before, after))
+            }
+            // Chains that didn't apply to the written content: no consistent
+            // whole-file state to export.
+            _ => None,
+        }
+    }
+}
+
+/// Lines a single record added and removed, from its own patch or snapshots.
+fn record_counts(r: &ToolUseResult) -> (usize, usize) {
+    if r.is_notebook_edit() {
+        if let (Some(before), Some(after)) = (&r.notebook.original_file, &r.notebook.updated_file) {
+            return compute_diff(&cell_sources(before).unwrap_or_default(), &cell_sources(after).unwrap_or_default());
+        }
+        let inserted = r.notebook.new_source.as_deref().filter(|_| r.notebook.edit_mode.as_deref() == Some("insert"));
+        return (inserted.map_or(0, |src| src.lines().count()), 0);
+    }
+    if let Some(patch) = r.structured_patch.as_ref().filter(|p| !p.is_empty()) {
+        let lines = patch.iter().flat_map(|h| &h.lines);
+        let (added, removed) = lines.fold((0, 0), |(a, d), l| match l.chars().next() {
+            Some('+') => (a + 1, d),
+            Some('-') => (a, d + 1),
+            _ => (a, d),
+        });
+        return (added, removed);
+    }
+    if let Some(content) = &r.content {
+        return compute_diff(r.original_file.as_deref().unwrap_or(""), content);
+    }
+    let pairs = r.old_string.as_deref().zip(r.new_string.as_deref()).into_iter()
+        .chain(r.edits.iter().flatten().map(|e| (e.old_string.as_str(), e.new_string.as_str())));
+    pairs.fold((0, 0), |(a, d), (old, new)| {
+        let (added, removed) = compute_diff(old, new);
+        (a + added, d + removed)
+    })
+}
+
+fn content_hash(s: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    s.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn join_lines(lines: &[String]) -> String {
+    lines.iter().map(|l| format!("{}\n", l)).collect()
 }
 
 fn split_lines(s: &str) -> Vec<String> {
@@ -264,6 +1359,14 @@ fn is_creation_write(r: &ToolUseResult) -> bool {
         && r.original_file.as_deref().is_none_or(str::is_empty)
 }
 
+/// A Write to a file that didn't exist: creation-shaped patch data, or (for
+/// records without a structuredPatch) an empty or missing originalFile.
+fn is_created(r: &ToolUseResult) -> bool {
+    r.content.is_some()
+        && r.structured_patch.as_deref().is_none_or(|p| p.is_empty())
+        && r.original_file.as_deref().is_none_or(str::is_empty)
+}
+
 /// structuredPatch lines store tabs as two spaces; file snapshots keep real tabs.
 fn norm_ws(s: &str) -> Cow<'_, str> {
     if s.contains('\t') { Cow::Owned(s.replace('\t', "  ")) } else { Cow::Borrowed(s) }
@@ -281,48 +1384,6 @@ fn join_normalized(lines: &[String]) -> String {
     out
 }
 
-/// Rebuild the file's content before the first record and after the last one.
-/// Anchored at the latest full snapshot (a non-empty originalFile holds the
-/// content just before its record; a Write's content holds the content just
-/// after); earlier patches are un-applied backward, later ones applied forward.
-fn patch_reconstruct(records: &[ToolUseResult]) -> Option<(Vec<String>, Vec<String>)> {
-    let orig_anchor = records
-        .iter()
-        .rposition(|r| r.original_file.as_deref().is_some_and(|s| !s.is_empty()));
-    let content_anchor = records.iter().rposition(|r| r.content.is_some());
-
-    // (records to un-apply backward, anchor snapshot, first record to apply forward)
-    let (backward, anchor, forward) = match (orig_anchor, content_anchor) {
-        (Some(i), Some(j)) if j >= i => (j + 1, split_lines(records[j].content.as_ref()?), j + 1),
-        (Some(i), _) => (i, split_lines(records[i].original_file.as_ref()?), i),
-        (None, Some(j)) => (j + 1, split_lines(records[j].content.as_ref()?), j + 1),
-        (None, None) => return None,
-    };
-
-    // A Write that creates a new file carries content but an empty patch and an
-    // empty/missing originalFile — an empty patch over non-empty content is only
-    // consistent with the file not having existed before. The empty patch holds
-    // no diff to un-apply, so the backward walk would wrongly leave `before`
-    // equal to the created content. The true prior state is empty.
-    if records.first().is_some_and(is_creation_write) {
-        let mut after = anchor;
-        for r in &records[forward..] {
-            after = apply_patch(&after, r.structured_patch.as_ref()?, false)?;
-        }
-        return Some((Vec::new(), after));
-    }
-
-    let mut before = anchor.clone();
-    for r in records[..backward].iter().rev() {
-        before = apply_patch(&before, r.structured_patch.as_ref()?, true)?;
-    }
-    let mut after = anchor;
-    for r in &records[forward..] {
-        after = apply_patch(&after, r.structured_patch.as_ref()?, false)?;
-    }
-    Some((before, after))
-}
-
 fn apply_patch(lines: &[String], hunks: &[Hunk], reverse: bool) -> Option<Vec<String>> {
     let mut out: Vec<String> = Vec::with_capacity(lines.len());
     let mut pos = 0usize;
@@ -365,70 +1426,51 @@ fn apply_patch(lines: &[String], hunks: &[Hunk], reverse: bool) -> Option<Vec<St
     Some(out)
 }
 
-fn legacy_diff(records: &[ToolUseResult]) -> (usize, usize) {
-    let mut file_original: Option<String> = None;
-    let mut file_final: Option<String> = None;
-    let mut chains: Vec<(String, String)> = Vec::new();
-
-    for result in records {
-        if let Some(content) = &result.content {
-            if file_original.is_none() {
-                file_original = Some(result.original_file.clone().unwrap_or_default());
-            }
-            file_final = Some(content.clone());
-            chains.clear();
-        } else if let (Some(old_str), Some(new_str)) = (&result.old_string, &result.new_string) {
-            let applied_to_write = if let Some(final_content) = file_final.as_mut() {
-                if final_content.contains(old_str.as_str()) {
-                    *final_content = final_content.replacen(old_str, new_str, 1);
-                    true
-                } else {
-                    false
-                }
-            } else {
-                false
-            };
-
-            if !applied_to_write {
-                let mut found = false;
-                for (_original, current) in chains.iter_mut() {
-                    if current.contains(old_str.as_str()) {
-                        *current = current.replacen(old_str, new_str, 1);
-                        found = true;
-                        break;
-                    }
-                }
+/// Applies one old/new replacement to the written content if it's there,
+/// otherwise to the edit chain it continues (or starts a new chain).
+fn replay_edit(old_str: &str, new_str: &str, file_final: &mut Option<String>, chains: &mut Vec<(String, String)>) {
+    if let Some(final_content) = file_final.as_mut()
+        && final_content.contains(old_str) {
+            *final_content = final_content.replacen(old_str, new_str, 1);
+            return;
+        }
 
-                if !found {
-                    // Absorb any existing chains whose current is inside this edit's old_string
-                    let mut resolved_old = old_str.clone();
-                    chains.retain(|(orig, cur)| {
-                        if resolved_old.contains(cur.as_str()) {
-                            resolved_old = resolved_old.replacen(cur, orig, 1);
-                            false
-                        } else {
-                            true
-                        }
-                    });
-                    chains.push((resolved_old, new_str.clone()));
-                }
-            }
+    for (_original, current) in chains.iter_mut() {
+        if current.contains(old_str) {
+            *current = current.replacen(old_str, new_str, 1);
+            return;
         }
     }
 
-    let mut added = 0;
-    let mut removed = 0;
-    for (original, final_content) in &chains {
-        let (a, r) = compute_diff(original, final_content);
-        added += a;
-        removed += r;
+    // Absorb any existing chains whose current is inside this edit's old_string
+    let mut resolved_old = old_str.to_string();
+    chains.retain(|(orig, cur)| {
+        if resolved_old.contains(cur.as_str()) {
+            resolved_old = resolved_old.replacen(cur, orig, 1);
+            false
+        } else {
+            true
+        }
+    });
+    chains.push((resolved_old, new_str.to_string()));
+}
+
+/// The notebook's cell sources, one after another, each ending in a newline.
+fn cell_sources(notebook: &str) -> Option<String> {
+    if notebook.is_empty() {
+        return Some(String::new());
     }
-    if let (Some(original), Some(final_content)) = (&file_original, &file_final) {
-        let (a, r) = compute_diff(original, final_content);
-        added += a;
-        removed += r;
+    let v: serde_json::Value = serde_json::from_str(notebook).ok()?;
+    let mut out = String::new();
+    for cell in v.get("cells")?.as_array()? {
+        let source = match cell.get("source") {
+            Some(serde_json::Value::String(s)) => s.clone(),
+            Some(serde_json::Value::Array(lines)) => lines.iter().filter_map(|l| l.as_str()).collect(),
+            _ => continue,
+        };
+        out.push_str(&ensure_trailing_newline(&source));
     }
-    (added, removed)
+    Some(out)
 }
 
 fn ensure_trailing_newline(s: &str) -> Cow<'_, str> {
@@ -17,3 +17,66 @@ pub fn jsonl_files(dir: &Path) -> Vec<PathBuf> {
     }
     out
 }
+
+/// Modification time of `path` in milliseconds since the epoch; None if it
+/// doesn't exist.
+pub fn mtime_ms(path: &Path) -> Option<u128> {
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    modified.duration_since(std::time::UNIX_EPOCH).ok().map(|d| d.as_millis())
+}
+
+/// The current time in milliseconds since the epoch.
+pub fn now_ms() -> u128 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0)
+}
+
+/// Expands a leading `~/` to `$HOME`. Other paths are returned unchanged.
+pub fn expand_tilde(p: &str) -> PathBuf {
+    if let Some(rest) = p.strip_prefix("~/")
+        && let Ok(home) = std::env::var("HOME") {
+            return PathBuf::from(home).join(rest);
+        }
+    PathBuf::from(p)
+}
+
+/// Shell-style glob match over `/`-separated paths: `*` and `?` stay within
+/// one component, `**` spans any number of components (including none).
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    fn go(p: &[u8], t: &[u8]) -> bool {
+        match p {
+            [] => t.is_empty(),
+            [b'*', b'*', b'/', rest @ ..] => {
+                // `**/` matches zero or more leading components.
+                go(rest, t) || (0..t.len()).any(|i| t[i] == b'/' && go(rest, &t[i + 1..]))
+            }
+            [b'*', b'*', rest @ ..] => (0..=t.len()).any(|i| go(rest, &t[i..])),
+            [b'*', rest @ ..] => {
+                (0..=t.len()).take_while(|&i| i == 0 || t[i - 1] != b'/').any(|i| go(rest, &t[i..]))
+            }
+            [b'?', rest @ ..] => t.first().is_some_and(|&c| c != b'/') && go(rest, &t[1..]),
+            [c, rest @ ..] => t.first() == Some(c) && go(rest, &t[1..]),
+        }
+    }
+    go(pattern.as_bytes(), text.as_bytes())
+}
+
+/// Writes `bytes` to a temp file beside `path` and renames it into place, so
+/// readers never see a partial file. Creates the parent directory if needed.
+pub fn atomic_write(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent()
+        && !parent.as_os_str().is_empty() {
+            let _ = fs::create_dir_all(parent);
+        }
+    // Per-PID temp name so two processes writing the same file (two renders,
+    // or host + devcontainer on a bind mount) don't clobber each other's temp
+    // file mid-write.
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(format!(".{}.tmp", std::process::id()));
+    let tmp = path.with_file_name(tmp_name);
+    fs::write(&tmp, bytes)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
@@ -1,53 +1,634 @@
+use crate::cache;
+use crate::config::GitConfig;
+use crate::fsutil;
+use crate::process;
+use crate::vcs::Vcs;
+use serde::{Deserialize, Serialize};
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
 
-pub fn get_git_info(cwd: &str) -> (String, Option<String>) {
-    match find_git_dir(cwd) {
-        Some(git_dir) => (
-            read_git_branch(&git_dir).unwrap_or_else(|| "no-git".to_string()),
-            read_remote_url(&git_dir),
-        ),
-        None => ("no-git".to_string(), None),
-    }
-}
-
-fn read_remote_url(git_dir: &std::path::Path) -> Option<String> {
-    let config = std::fs::read_to_string(git_dir.join("config")).ok()?;
-    let mut in_origin = false;
-    for line in config.lines() {
-        let trimmed = line.trim();
-        if trimmed == "[remote \"origin\"]" {
-            in_origin = true;
-        } else if trimmed.starts_with('[') {
-            in_origin = false;
-        } else if in_origin
-            && let Some(url) = trimmed.strip_prefix("url = ") {
-                return Some(normalize_remote_url(url));
+/// Upper bound on any `git` subprocess. The statusline re-renders constantly,
+/// so a slow repo (huge untracked tree, cold disk) must not stall it.
+const GIT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How long cached `git-status` and `diff` counts, hg's dirty flag and jj's
+/// `log` are trusted. Files edited outside the agent (an editor, a terminal)
+/// touch neither the index nor the transcript, so only age catches them.
+pub const STATUS_TTL: Duration = Duration::from_secs(5);
+
+/// A repository located from a working directory.
+struct Repo {
+    /// The checkout's top level; None for a bare repository.
+    work_tree: Option<PathBuf>,
+    /// Per-checkout state: HEAD, index, in-progress operations. For a linked
+    /// worktree this is `<common>/worktrees/<name>`.
+    git_dir: PathBuf,
+    /// Shared state: config, refs, packed-refs. Same as `git_dir` except in
+    /// linked worktrees, where `git_dir/commondir` points back to it.
+    common_dir: PathBuf,
+}
+
+impl Repo {
+    /// Where `git -C` should run: the work tree, or the git dir when bare.
+    fn run_dir(&self) -> &Path {
+        self.work_tree.as_deref().unwrap_or(&self.git_dir)
+    }
+
+    /// The linked worktree's name (its directory under `worktrees/`); None
+    /// for the main checkout.
+    fn worktree_name(&self) -> Option<String> {
+        if self.git_dir == self.common_dir {
+            return None;
+        }
+        self.git_dir.file_name().and_then(|n| n.to_str()).map(String::from)
+    }
+}
+
+/// The git backend for the `dir` and `branch` fields.
+pub struct GitBackend {
+    repo: Repo,
+    /// The session's transcript, whose cache holds the `git status` counts.
+    transcript_path: String,
+    config: Vec<ConfigEntry>,
+    remote: Option<Remote>,
+    links: Option<LinkTemplates>,
+}
+
+impl GitBackend {
+    pub fn open(cwd: &str, transcript_path: &str, settings: &GitConfig) -> Option<GitBackend> {
+        let repo = find_repo(cwd)?;
+        let config = read_config(&repo);
+        let remote = read_remote(&repo.git_dir, &config, settings);
+        let links = remote.as_ref().map(|r| link_templates(&r.host, settings));
+        Some(GitBackend { repo, transcript_path: transcript_path.to_string(), config, remote, links })
+    }
+}
+
+impl Vcs for GitBackend {
+    /// `main`, `feature-x|REBASE 3/7`, `v1.2.8`, `BARE:main`.
+    fn branch(&self) -> Option<String> {
+        let branch = read_git_branch(&self.repo, &self.transcript_path)?;
+        Some(if self.repo.work_tree.is_none() { format!("BARE:{}", branch) } else { branch })
+    }
+
+    fn change_id(&self) -> Option<String> {
+        resolve_ref(&self.repo, "HEAD").map(|sha| sha.get(..7).unwrap_or(&sha).to_string())
+    }
+
+    fn remote_url(&self) -> Option<String> {
+        self.remote.as_ref().map(|r| r.web_url.clone())
+    }
+
+    /// From the counts `git-status` caches, so the marker costs no extra
+    /// `git status` call. Unknown in a bare repository.
+    fn is_dirty(&self) -> Option<bool> {
+        repo_status(&self.repo, &self.transcript_path).map(|s| !s.is_clean())
+    }
+
+    fn links(&self) -> (Option<String>, Option<String>) {
+        let (Some(remote), Some(links)) = (&self.remote, &self.links) else { return (None, None) };
+        let op = read_operation(&self.repo.git_dir);
+        match current_head(&self.repo.git_dir, op.as_ref()) {
+            Some(Head::Branch(local)) => {
+                // Link the name the branch has on the remote, which can differ
+                // from the local one (`git push -u origin local:remote`).
+                let branch = config_get(&self.config, "branch", Some(&local), "merge")
+                    .map(|m| m.strip_prefix("refs/heads/").unwrap_or(m).to_string())
+                    .unwrap_or(local);
+                let branch_url = expand_link(&links.branch, &remote.web_url, &[("branch", &branch)]);
+                let compare_url = default_branch(&self.repo, &remote.name)
+                    .filter(|base| *base != branch)
+                    .map(|base| expand_link(&links.compare, &remote.web_url, &[("base", &base), ("branch", &branch)]));
+                (Some(branch_url), compare_url)
             }
+            Some(Head::Detached(sha)) => (Some(expand_link(&links.commit, &remote.web_url, &[("sha", &sha)])), None),
+            None => (None, None),
+        }
     }
-    None
 }
 
-fn normalize_remote_url(url: &str) -> String {
-    // git@github.com:user/repo.git -> https://github.com/user/repo
-    if let Some(rest) = url.strip_prefix("git@") {
-        let rest = rest.replace(':', "/");
-        let rest = rest.strip_suffix(".git").unwrap_or(&rest);
-        return format!("https://{}", rest);
+/// Web URL for a git repository's remote, for backends (jj) that keep their
+/// remotes in a backing git repository at `git_dir`.
+pub fn remote_url_for_git_dir(git_dir: &Path, settings: &GitConfig) -> Option<String> {
+    let repo = Repo { work_tree: None, git_dir: git_dir.to_path_buf(), common_dir: git_dir.to_path_buf() };
+    read_remote(&repo.git_dir, &read_config(&repo), settings).map(|r| r.web_url)
+}
+
+/// Web URL for a clone URL in any form git accepts, for backends (hg) that
+/// store remote URLs themselves.
+pub fn web_url(url: &str, settings: &GitConfig) -> Option<String> {
+    normalize_remote_url(url, settings).map(|(_, web)| web)
+}
+
+/// URL templates for a host's branch, commit and compare pages. `{repo}` is
+/// the repository's web URL; `{branch}`, `{base}` and `{sha}` are filled in.
+struct LinkTemplates {
+    branch: String,
+    commit: String,
+    compare: String,
+}
+
+/// The link layout for `host`: a `git.hosts` entry's `kind` and explicit
+/// templates win; otherwise the kind is guessed from the host name, falling
+/// back to GitHub's layout, which most forges also accept.
+fn link_templates(host: &str, settings: &GitConfig) -> LinkTemplates {
+    let host_cfg = settings.hosts.get(host);
+    let kind = host_cfg.and_then(|h| h.kind.as_deref()).map(str::to_ascii_lowercase).unwrap_or_else(|| {
+        ["gitlab", "bitbucket", "gitea", "codeberg"]
+            .into_iter()
+            .find(|k| host.contains(k))
+            .unwrap_or("github")
+            .to_string()
+    });
+    let (branch, commit, compare) = match kind.as_str() {
+        "gitlab" => ("{repo}/-/tree/{branch}", "{repo}/-/commit/{sha}", "{repo}/-/compare/{base}...{branch}"),
+        "bitbucket" => ("{repo}/src/{branch}", "{repo}/commits/{sha}", "{repo}/branches/compare/{branch}%0D{base}"),
+        "gitea" | "codeberg" | "forgejo" => ("{repo}/src/branch/{branch}", "{repo}/commit/{sha}", "{repo}/compare/{base}...{branch}"),
+        _ => ("{repo}/tree/{branch}", "{repo}/commit/{sha}", "{repo}/compare/{base}...{branch}"),
+    };
+    let pick = |custom: Option<&String>, builtin: &str| custom.cloned().unwrap_or_else(|| builtin.to_string());
+    LinkTemplates {
+        branch: pick(host_cfg.and_then(|h| h.branch_url.as_ref()), branch),
+        commit: pick(host_cfg.and_then(|h| h.commit_url.as_ref()), commit),
+        compare: pick(host_cfg.and_then(|h| h.compare_url.as_ref()), compare),
+    }
+}
+
+fn expand_link(template: &str, repo_url: &str, vars: &[(&str, &str)]) -> String {
+    let mut out = template.replace("{repo}", repo_url);
+    for (name, value) in vars {
+        out = out.replace(&format!("{{{}}}", name), &encode_ref(value));
     }
-    // https://github.com/user/repo.git -> https://github.com/user/repo
-    url.strip_suffix(".git").unwrap_or(url).to_string()
+    out
+}
+
+/// Percent-encodes the characters in a ref name that would otherwise end or
+/// corrupt a URL path. `/` is kept: forges expect it literally in branch paths.
+fn encode_ref(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            '%' | '#' | '?' | ' ' | '"' | '<' | '>' | '\\' | '^' | '`' | '{' | '|' | '}' => {
+                let mut buf = [0u8; 4];
+                for b in c.encode_utf8(&mut buf).bytes() {
+                    out.push_str(&format!("%{:02X}", b));
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// The remote's default branch: where `refs/remotes/<remote>/HEAD` points
+/// (set by clone or `git remote set-head`), else `main` or `master` if the
+/// remote has one.
+fn default_branch(repo: &Repo, remote: &str) -> Option<String> {
+    let prefix = format!("refs/remotes/{}/", remote);
+    if let Ok(head) = fs::read_to_string(repo.common_dir.join(&prefix).join("HEAD"))
+        && let Some(target) = head.trim().strip_prefix("ref: ").and_then(|t| t.strip_prefix(&prefix)) {
+            return Some(target.to_string());
+        }
+    ["main", "master"]
+        .into_iter()
+        .find(|b| resolve_ref(repo, &format!("{}{}", prefix, b)).is_some())
+        .map(String::from)
+}
+
+/// Working-tree change counts, as in `git status`.
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct GitStatus {
+    pub staged: usize,
+    pub modified: usize,
+    pub untracked: usize,
+    pub conflicted: usize,
+}
+
+impl GitStatus {
+    pub fn is_clean(&self) -> bool {
+        *self == GitStatus::default()
+    }
+}
+
+/// Counts staged/modified/untracked/conflicted paths. Cached in the session
+/// cache: the index mtime changes on every stage, commit and checkout, and the
+/// transcript grows whenever the agent edits a file, so a render where neither
+/// moved reuses the previous counts without spawning git, for up to
+/// `STATUS_TTL`.
+pub fn get_git_status(cwd: &str, transcript_path: &str) -> Option<GitStatus> {
+    repo_status(&find_repo(cwd)?, transcript_path)
+}
+
+fn repo_status(repo: &Repo, transcript_path: &str) -> Option<GitStatus> {
+    let work_tree = repo.work_tree.as_deref()?;
+    let index_mtime_ms = index_mtime_ms(repo);
+    let transcript_size = cache::get_file_size(transcript_path);
+    let now_ms = fsutil::now_ms();
+
+    let cached = cache::load_raw(transcript_path);
+    if let Some(g) = cached.as_ref().and_then(|c| c.git.status.as_ref())
+        && g.index_mtime_ms == index_mtime_ms
+        && g.transcript_size == transcript_size
+        && now_ms.saturating_sub(g.checked_ms) < STATUS_TTL.as_millis()
+        && g.work_tree == work_tree.to_string_lossy() {
+            return g.status.clone();
+        }
+
+    // A failure is cached too: a repo too big for `GIT_TIMEOUT` would
+    // otherwise pay it on every render.
+    let status = run_git(work_tree, &["status", "--porcelain=v2", "--untracked-files=normal"])
+        .map(|output| parse_porcelain_v2(&output));
+
+    cache::update(transcript_path, |c| {
+        c.git.status = Some(cache::GitStatusCache {
+            work_tree: work_tree.to_string_lossy().into_owned(),
+            index_mtime_ms,
+            transcript_size,
+            checked_ms: now_ms,
+            status: status.clone(),
+        });
+    });
+
+    status
+}
+
+fn index_mtime_ms(repo: &Repo) -> u128 {
+    fs::metadata(repo.git_dir.join("index"))
+        .ok()
+        .and_then(|m| m.modified().ok())
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_millis())
+        .unwrap_or(0)
 }
 
-fn find_git_dir(cwd: &str) -> Option<std::path::PathBuf> {
-    let mut dir = std::path::Path::new(cwd);
+/// Git's empty tree: the base for a repository without commits yet.
+const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+/// The commit HEAD pointed to the first time the session's diff was shown,
+/// recorded in the session cache (the empty tree if HEAD was unborn).
+/// Recorded afresh if the session moves to another repository.
+pub fn get_session_base(cwd: &str, transcript_path: &str) -> Option<String> {
+    let repo = find_repo(cwd)?;
+    let work_tree = repo.work_tree.as_deref()?.to_string_lossy().into_owned();
+
+    let cached = cache::load_raw(transcript_path);
+    if let Some(b) = cached.as_ref().and_then(|c| c.git.session_base.as_ref())
+        && b.work_tree == work_tree {
+            return Some(b.sha.clone());
+        }
+
+    let sha = resolve_ref(&repo, "HEAD").unwrap_or_else(|| EMPTY_TREE.to_string());
+    cache::update(transcript_path, |c| c.git.session_base = Some(cache::SessionBaseCache { work_tree, sha: sha.clone() }));
+
+    Some(sha)
+}
+
+/// Lines added and removed in each file between `base` (HEAD when None) and
+/// the working tree, staged or not, keyed by absolute path. Untracked files
+/// count in full as added; binary files are left out. Cached on the same
+/// terms as `get_git_status`.
+pub fn get_git_diff(cwd: &str, transcript_path: &str, base: Option<&str>) -> Option<Vec<(String, usize, usize)>> {
+    let repo = find_repo(cwd)?;
+    let work_tree = repo.work_tree.as_deref()?;
+    let base = match base {
+        Some(b) => b.to_string(),
+        None => resolve_ref(&repo, "HEAD").unwrap_or_else(|| EMPTY_TREE.to_string()),
+    };
+    let index_mtime_ms = index_mtime_ms(&repo);
+    let transcript_size = cache::get_file_size(transcript_path);
+    let now_ms = fsutil::now_ms();
+
+    let cached = cache::load_raw(transcript_path);
+    if let Some(g) = cached.as_ref().and_then(|c| c.git.diff.as_ref())
+        && g.base == base
+        && g.index_mtime_ms == index_mtime_ms
+        && g.transcript_size == transcript_size
+        && now_ms.saturating_sub(g.checked_ms) < STATUS_TTL.as_millis()
+        && g.work_tree == work_tree.to_string_lossy() {
+            return Some(g.files.clone());
+        }
+
+    let numstat = run_git(work_tree, &["diff", "--numstat", "-z", "--no-renames", &base, "--"])?;
+    let mut files = parse_numstat(work_tree, &numstat);
+    let untracked = run_git(work_tree, &["ls-files", "-z", "--others", "--exclude-standard"])?;
+    for rel in untracked.split('\0').filter(|p| !p.is_empty()) {
+        let path = work_tree.join(rel);
+        let Ok(bytes) = fs::read(&path) else { continue };
+        if bytes.contains(&0) {
+            continue;
+        }
+        let lines = bytes.iter().filter(|&&b| b == b'\n').count() + usize::from(!bytes.is_empty() && !bytes.ends_with(b"\n"));
+        files.push((path.to_string_lossy().into_owned(), lines, 0));
+    }
+
+    cache::update(transcript_path, |c| {
+        c.git.diff = Some(cache::GitDiffCache {
+            work_tree: work_tree.to_string_lossy().into_owned(),
+            base,
+            index_mtime_ms,
+            transcript_size,
+            checked_ms: now_ms,
+            files: files.clone(),
+        });
+    });
+
+    Some(files)
+}
+
+/// `git diff --numstat -z` entries: `added<TAB>removed<TAB>path<NUL>`, with
+/// `-` counts for binary files.
+fn parse_numstat(work_tree: &Path, output: &str) -> Vec<(String, usize, usize)> {
+    let mut files = Vec::new();
+    for entry in output.split('\0') {
+        let mut parts = entry.splitn(3, '\t');
+        if let (Some(added), Some(removed), Some(path)) = (parts.next(), parts.next(), parts.next())
+            && let (Ok(added), Ok(removed)) = (added.parse(), removed.parse()) {
+                files.push((work_tree.join(path).to_string_lossy().into_owned(), added, removed));
+            }
+    }
+    files
+}
+
+/// Tallies `git status --porcelain=v2` entries. Ordinary (`1`) and rename
+/// (`2`) entries carry an `XY` pair: X is the index side, Y the work tree.
+fn parse_porcelain_v2(output: &str) -> GitStatus {
+    let mut status = GitStatus::default();
+    for line in output.lines() {
+        let mut parts = line.splitn(3, ' ');
+        match (parts.next(), parts.next()) {
+            (Some("1" | "2"), Some(xy)) => {
+                let mut xy = xy.chars();
+                if xy.next().is_some_and(|x| x != '.') {
+                    status.staged += 1;
+                }
+                if xy.next().is_some_and(|y| y != '.') {
+                    status.modified += 1;
+                }
+            }
+            (Some("u"), _) => status.conflicted += 1,
+            (Some("?"), _) => status.untracked += 1,
+            _ => {}
+        }
+    }
+    status
+}
+
+/// Runs `git -C <dir> <args>` without taking optional locks (so it never
+/// contends with the agent's own git commands), bounded by `GIT_TIMEOUT`.
+/// Returns stdout on success.
+fn run_git(dir: &Path, args: &[&str]) -> Option<String> {
+    let mut cmd = Command::new("git");
+    cmd.arg("--no-optional-locks").arg("-C").arg(dir).args(args);
+    process::output_with_timeout(&mut cmd, GIT_TIMEOUT)
+}
+
+/// A remote resolved to its web presence.
+struct Remote {
+    name: String,
+    host: String,
+    web_url: String,
+}
+
+/// The remote the current branch pushes to: its upstream's remote if
+/// configured, else `origin`, else the first remote defined.
+fn read_remote(git_dir: &Path, config: &[ConfigEntry], settings: &GitConfig) -> Option<Remote> {
+    let upstream_remote = head_branch(git_dir)
+        .and_then(|b| config_get(config, "branch", Some(&b), "remote").map(String::from))
+        .filter(|r| r != ".");
+    let name = upstream_remote
+        .filter(|r| config_get(config, "remote", Some(r), "url").is_some())
+        .or_else(|| config_get(config, "remote", Some("origin"), "url").map(|_| "origin".to_string()))
+        .or_else(|| config.iter().find(|e| e.section == "remote" && e.key == "url").and_then(|e| e.subsection.clone()))?;
+    let url = config_get(config, "remote", Some(&name), "url")?;
+    let (host, web_url) = normalize_remote_url(&apply_instead_of(config, url), settings)?;
+    Some(Remote { name, host, web_url })
+}
+
+/// One `key = value` line of a git config file, with the section it sits in.
+/// Section and key names are case-insensitive in git, so both are lowercased;
+/// the subsection (`[branch "Feature"]`) is case-sensitive and kept verbatim.
+struct ConfigEntry {
+    section: String,
+    subsection: Option<String>,
+    key: String,
+    value: String,
+}
+
+/// Nesting limit for `include.path`, matching git's own guard against cycles.
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+/// The effective config for a repository, in git's precedence order: the
+/// global files (`$XDG_CONFIG_HOME/git/config`, `~/.gitconfig`) then the
+/// repository's `config` (shared by all worktrees, so read from the common
+/// dir), each with its `include`/`includeIf` files expanded in place. Later
+/// entries override earlier ones (see `config_get`).
+fn read_config(repo: &Repo) -> Vec<ConfigEntry> {
+    let git_dir = repo.git_dir.as_path();
+    let mut entries = Vec::new();
+    let xdg = std::env::var("XDG_CONFIG_HOME")
+        .ok()
+        .filter(|x| !x.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var("HOME").ok().map(|h| PathBuf::from(h).join(".config")));
+    if let Some(xdg) = xdg {
+        read_config_file(&xdg.join("git/config"), git_dir, &mut entries, 0);
+    }
+    if let Ok(home) = std::env::var("HOME") {
+        read_config_file(&PathBuf::from(home).join(".gitconfig"), git_dir, &mut entries, 0);
+    }
+    read_config_file(&repo.common_dir.join("config"), git_dir, &mut entries, 0);
+    entries
+}
+
+/// Parses one config file into `entries`. Handles the subset git itself
+/// writes: section headers with optional quoted subsections, `key = value`
+/// lines, and `#`/`;` comments. Missing or unreadable files add nothing.
+fn read_config_file(path: &Path, git_dir: &Path, entries: &mut Vec<ConfigEntry>, depth: usize) {
+    let Ok(content) = fs::read_to_string(path) else { return };
+    let mut section = String::new();
+    let mut subsection: Option<String> = None;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.split(']').next()) {
+            match header.split_once(char::is_whitespace) {
+                Some((name, sub)) => {
+                    section = name.to_ascii_lowercase();
+                    subsection = Some(sub.trim().trim_matches('"').to_string());
+                }
+                None => {
+                    section = header.to_ascii_lowercase();
+                    subsection = None;
+                }
+            }
+            continue;
+        }
+        let (key, value) = line.split_once('=').unwrap_or((line, "true"));
+        let key = key.trim().to_ascii_lowercase();
+        let value = value.trim().trim_matches('"').to_string();
+
+        let is_include = key == "path"
+            && (section == "include"
+                || (section == "includeif"
+                    && subsection.as_deref().is_some_and(|c| include_condition_holds(c, path, git_dir))));
+        if is_include {
+            if depth < MAX_INCLUDE_DEPTH {
+                read_config_file(&resolve_config_path(&value, path), git_dir, entries, depth + 1);
+            }
+            continue;
+        }
+
+        entries.push(ConfigEntry { section: section.clone(), subsection: subsection.clone(), key, value });
+    }
+}
+
+/// An included path is `~/`-expanded, and relative paths are taken relative
+/// to the including file's directory.
+fn resolve_config_path(value: &str, including: &Path) -> PathBuf {
+    let p = crate::fsutil::expand_tilde(value);
+    if p.is_absolute() {
+        p
+    } else {
+        including.parent().unwrap_or(Path::new("")).join(p)
+    }
+}
+
+/// Evaluates an `includeIf` condition: `gitdir:`, `gitdir/i:` (against the
+/// repository's git dir) and `onbranch:` (against the checked-out branch).
+fn include_condition_holds(cond: &str, config_path: &Path, git_dir: &Path) -> bool {
+    if let Some(pattern) = cond.strip_prefix("onbranch:") {
+        let pattern = if pattern.ends_with('/') { format!("{}**", pattern) } else { pattern.to_string() };
+        return head_branch(git_dir).is_some_and(|b| crate::fsutil::glob_match(&pattern, &b));
+    }
+    let (pattern, icase) = match (cond.strip_prefix("gitdir:"), cond.strip_prefix("gitdir/i:")) {
+        (Some(p), _) => (p, false),
+        (_, Some(p)) => (p, true),
+        _ => return false,
+    };
+
+    // git's rules: `~/` is home, `./` is relative to the config file, any
+    // other relative pattern matches at any depth, and a trailing `/` matches
+    // everything beneath it.
+    let mut pattern = if let Some(rest) = pattern.strip_prefix("./") {
+        config_path.parent().unwrap_or(Path::new("")).join(rest).to_string_lossy().into_owned()
+    } else if pattern.starts_with("~/") {
+        crate::fsutil::expand_tilde(pattern).to_string_lossy().into_owned()
+    } else if pattern.starts_with('/') {
+        pattern.to_string()
+    } else {
+        format!("**/{}", pattern)
+    };
+    if pattern.ends_with('/') {
+        pattern.push_str("**");
+    }
+
+    let dir = fs::canonicalize(git_dir).unwrap_or_else(|_| git_dir.to_path_buf());
+    let dir = dir.to_string_lossy();
+    if icase {
+        crate::fsutil::glob_match(&pattern.to_lowercase(), &dir.to_lowercase())
+    } else {
+        crate::fsutil::glob_match(&pattern, &dir)
+    }
+}
+
+/// The effective value of `section.subsection.key`: the last one seen, so
+/// repository config overrides global config as in git.
+fn config_get<'a>(config: &'a [ConfigEntry], section: &str, subsection: Option<&str>, key: &str) -> Option<&'a str> {
+    config
+        .iter()
+        .rfind(|e| e.section == section && e.subsection.as_deref() == subsection && e.key == key)
+        .map(|e| e.value.as_str())
+}
+
+/// Applies `url.<base>.insteadOf` rewrites: the longest matching prefix is
+/// replaced by its base, as git does before connecting.
+fn apply_instead_of(config: &[ConfigEntry], url: &str) -> String {
+    config
+        .iter()
+        .filter(|e| e.section == "url" && e.key == "insteadof" && url.starts_with(e.value.as_str()))
+        .max_by_key(|e| e.value.len())
+        .and_then(|e| Some(format!("{}{}", e.subsection.as_deref()?, &url[e.value.len()..])))
+        .unwrap_or_else(|| url.to_string())
+}
+
+/// Converts a clone URL into the repository's web URL. Understands scp-style
+/// (`git@host:owner/repo.git`), `ssh://`, `git://` and `http(s)://` forms;
+/// credentials and the `.git` suffix are dropped, and so are ports except on
+/// HTTP(S) URLs, where the web server listens on the same one. Local-path
+/// remotes have no web page and yield None. A `git.hosts` entry for the host
+/// replaces the default `https://<host>/<path>`. Returns (host, web URL).
+fn normalize_remote_url(url: &str, settings: &GitConfig) -> Option<(String, String)> {
+    let (scheme, authority, path) = parse_remote_url(url)?;
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    let host = strip_port(&authority).to_string();
+    if host.is_empty() || path.is_empty() {
+        return None;
+    }
+    let web_url = match settings.hosts.get(&host).and_then(|h| h.web_url.as_deref()) {
+        Some(template) => template.replace("{host}", &host).replace("{path}", path),
+        None => format!("{}://{}/{}", scheme, authority, path),
+    };
+    Some((host, web_url))
+}
+
+/// Splits a clone URL into (web scheme, authority, path). The authority has
+/// any `user[:password]@` removed, and its `:port` too unless the URL is
+/// HTTP(S): an SSH or git-daemon port says nothing about the web server's.
+fn parse_remote_url(url: &str) -> Option<(&'static str, String, String)> {
+    let strip_auth = |authority: &str| -> String {
+        authority.rsplit_once('@').map(|(_, h)| h).unwrap_or(authority).to_string()
+    };
+
+    if let Some((scheme, rest)) = url.split_once("://") {
+        let web_scheme = match scheme {
+            "http" => "http",
+            "https" | "ssh" | "git" | "git+ssh" | "ssh+git" => "https",
+            _ => return None, // file://, ext::, etc.
+        };
+        let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+        let mut authority = strip_auth(authority);
+        if !matches!(scheme, "http" | "https") {
+            authority = strip_port(&authority).to_string();
+        }
+        return Some((web_scheme, authority, path.to_string()));
+    }
+
+    // scp-like syntax: [user@]host:path, recognized by a colon before any slash.
+    let colon = url.find(':')?;
+    if url[..colon].contains('/') {
+        return None;
+    }
+    Some(("https", strip_port(&strip_auth(&url[..colon])).to_string(), url[colon + 1..].to_string()))
+}
+
+/// `host` from `host:port`; anything else unchanged.
+fn strip_port(authority: &str) -> &str {
+    match authority.rsplit_once(':') {
+        Some((h, port)) if port.chars().all(|c| c.is_ascii_digit()) => h,
+        _ => authority,
+    }
+}
+
+fn find_repo(cwd: &str) -> Option<Repo> {
+    let mut dir = Path::new(cwd);
     let git_path = loop {
         let candidate = dir.join(".git");
         if candidate.exists() {
             break candidate;
         }
+        if is_bare_git_dir(dir) {
+            return Some(Repo { work_tree: None, git_dir: dir.to_path_buf(), common_dir: dir.to_path_buf() });
+        }
         dir = dir.parent()?;
     };
 
+    // Linked worktrees and submodules replace `.git` with a file naming the
+    // real git dir.
     let git_dir = match fs::read_to_string(&git_path) {
         Ok(content) if content.starts_with("gitdir: ") => {
             let gitdir = content.strip_prefix("gitdir: ").unwrap().trim();
@@ -56,20 +637,397 @@ fn find_git_dir(cwd: &str) -> Option<std::path::PathBuf> {
         _ => git_path,
     };
 
-    Some(git_dir)
+    // A linked worktree's git dir names the shared repository in `commondir`.
+    let common_dir = fs::read_to_string(git_dir.join("commondir"))
+        .ok()
+        .map(|c| git_dir.join(c.trim()))
+        .and_then(|c| fs::canonicalize(&c).ok().or(Some(c)))
+        .unwrap_or_else(|| git_dir.clone());
+
+    Some(Repo { work_tree: Some(dir.to_path_buf()), git_dir, common_dir })
+}
+
+/// A directory that is itself a bare repository: it has HEAD, objects and
+/// refs, and its config says `core.bare = true` (which rules out a regular
+/// `.git` dir the cwd happens to be inside).
+fn is_bare_git_dir(dir: &Path) -> bool {
+    if !dir.join("HEAD").is_file() || !dir.join("objects").is_dir() || !dir.join("refs").is_dir() {
+        return false;
+    }
+    let mut entries = Vec::new();
+    read_config_file(&dir.join("config"), dir, &mut entries, 0);
+    config_get(&entries, "core", None, "bare") == Some("true")
+}
+
+/// The superproject when `repo` is a submodule checkout: the nearest
+/// enclosing repository whose `.gitmodules` registers this work tree's path.
+/// Returns (superproject name, submodule path within it).
+fn find_superproject(repo: &Repo) -> Option<(String, String)> {
+    let work_tree = repo.work_tree.as_deref()?;
+    let parent = work_tree.parent()?;
+    let outer = find_repo(parent.to_str()?)?;
+    let outer_tree = outer.work_tree?;
+    let rel = work_tree.strip_prefix(&outer_tree).ok()?.to_string_lossy().into_owned();
+
+    let mut modules = Vec::new();
+    read_config_file(&outer_tree.join(".gitmodules"), &outer.git_dir, &mut modules, 0);
+    if !modules.iter().any(|e| e.section == "submodule" && e.key == "path" && e.value.trim_end_matches('/') == rel) {
+        return None;
+    }
+    let name = outer_tree.file_name()?.to_str()?.to_string();
+    Some((name, rel))
+}
+
+/// The top level of the checkout containing cwd (the submodule's own, not
+/// its superproject's). None outside a repository or in a bare one.
+pub fn get_repo_root(cwd: &str) -> Option<PathBuf> {
+    find_repo(cwd)?.work_tree
+}
+
+/// The linked worktree's name, for the `worktree` field. None in the main
+/// checkout or outside a repository.
+pub fn get_worktree(cwd: &str) -> Option<String> {
+    find_repo(cwd)?.worktree_name()
+}
+
+/// The superproject (`monorepo/libs/parser`) when cwd is inside a submodule,
+/// for the `submodule` field.
+pub fn get_submodule(cwd: &str) -> Option<String> {
+    let (name, rel) = find_superproject(&find_repo(cwd)?)?;
+    Some(format!("{}/{}", name, rel))
 }
 
-fn read_git_branch(git_dir: &std::path::Path) -> Option<String> {
-    if git_dir.join("rebase-merge").exists() || git_dir.join("rebase-apply").exists() {
-        return Some("rebasing".to_string());
+/// The branch name, suffixed with any in-progress operation
+/// (`feature-x|REBASE 3/7`, `main|MERGING`).
+fn read_git_branch(repo: &Repo, transcript_path: &str) -> Option<String> {
+    let op = read_operation(&repo.git_dir);
+    let branch = match current_head(&repo.git_dir, op.as_ref())? {
+        Head::Branch(name) => name,
+        Head::Detached(sha) => describe_detached(repo, &sha, transcript_path),
+    };
+    match op {
+        Some(op) => Some(format!("{}|{}", branch, op.label())),
+        None => Some(branch),
     }
+}
+
+enum Head {
+    Branch(String),
+    Detached(String),
+}
 
+/// What HEAD refers to. A rebase detaches HEAD; the branch being rebased is
+/// recorded in the operation's head-name and reported instead.
+fn current_head(git_dir: &Path, op: Option<&Operation>) -> Option<Head> {
+    if let Some(name) = op.and_then(|o| o.head_name.as_deref()) {
+        return Some(Head::Branch(name.strip_prefix("refs/heads/").unwrap_or(name).to_string()));
+    }
     let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
     match head.strip_prefix("ref: refs/heads/") {
         Some(branch) => {
-            let branch = branch.trim().to_string();
-            if branch.is_empty() { None } else { Some(branch) }
+            let branch = branch.trim();
+            if branch.is_empty() { None } else { Some(Head::Branch(branch.to_string())) }
+        }
+        None => Some(Head::Detached(head.trim().to_string())),
+    }
+}
+
+/// An operation git is in the middle of, as left on disk between commands.
+struct Operation {
+    name: &'static str,
+    /// (current step, total steps), for rebases and `git am`.
+    progress: Option<(usize, usize)>,
+    /// The ref being rebased (`refs/heads/feature-x`); None outside rebases
+    /// or for a rebase started on a detached HEAD.
+    head_name: Option<String>,
+}
+
+impl Operation {
+    fn label(&self) -> String {
+        match self.progress {
+            Some((step, total)) => format!("{} {}/{}", self.name, step, total),
+            None => self.name.to_string(),
+        }
+    }
+}
+
+/// Detects the in-progress operation with the same precedence as git's own
+/// prompt script: rebase, then merge, cherry-pick, revert, bisect.
+fn read_operation(git_dir: &Path) -> Option<Operation> {
+    let read = |rel: &str| fs::read_to_string(git_dir.join(rel)).ok().map(|s| s.trim().to_string());
+    let progress = |step: &str, total: &str| Some((read(step)?.parse().ok()?, read(total)?.parse().ok()?));
+    let head_name = |rel: &str| read(rel).filter(|n| n.starts_with("refs/"));
+
+    let rebase_merge = git_dir.join("rebase-merge");
+    if rebase_merge.is_dir() {
+        let name = if rebase_merge.join("interactive").exists() { "REBASE-i" } else { "REBASE" };
+        return Some(Operation {
+            name,
+            progress: progress("rebase-merge/msgnum", "rebase-merge/end"),
+            head_name: head_name("rebase-merge/head-name"),
+        });
+    }
+    let rebase_apply = git_dir.join("rebase-apply");
+    if rebase_apply.is_dir() {
+        let name = if rebase_apply.join("rebasing").exists() {
+            "REBASE"
+        } else if rebase_apply.join("applying").exists() {
+            "AM"
+        } else {
+            "AM/REBASE"
+        };
+        return Some(Operation {
+            name,
+            progress: progress("rebase-apply/next", "rebase-apply/last"),
+            head_name: head_name("rebase-apply/head-name"),
+        });
+    }
+
+    let name = if git_dir.join("MERGE_HEAD").exists() {
+        "MERGING"
+    } else if git_dir.join("CHERRY_PICK_HEAD").exists() {
+        "CHERRY-PICKING"
+    } else if git_dir.join("REVERT_HEAD").exists() {
+        "REVERTING"
+    } else if git_dir.join("BISECT_LOG").exists() {
+        "BISECTING"
+    } else {
+        return None;
+    };
+    Some(Operation { name, progress: None, head_name: None })
+}
+
+/// The in-progress operation on its own (`REBASE-i 3/7`), for the `git-state`
+/// field. None when the repo is idle or there is no repo.
+pub fn get_git_state(cwd: &str) -> Option<String> {
+    let repo = find_repo(cwd)?;
+    read_operation(&repo.git_dir).map(|op| op.label())
+}
+
+/// The branch HEAD points at, or None when detached.
+fn head_branch(git_dir: &Path) -> Option<String> {
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let branch = head.strip_prefix("ref: refs/heads/")?.trim();
+    if branch.is_empty() { None } else { Some(branch.to_string()) }
+}
+
+/// Resolves a full ref name (`refs/heads/main`) to its commit SHA, following
+/// symbolic refs. Loose ref files take precedence over `packed-refs`, as in git.
+fn resolve_ref(repo: &Repo, name: &str) -> Option<String> {
+    let mut name = name.to_string();
+    for _ in 0..8 {
+        match fs::read_to_string(ref_dir(repo, &name).join(&name)) {
+            Ok(content) => {
+                let content = content.trim();
+                match content.strip_prefix("ref: ") {
+                    Some(target) => name = target.to_string(),
+                    None => return Some(content.to_string()),
+                }
+            }
+            Err(_) => return read_packed_ref(&repo.common_dir, &name),
+        }
+    }
+    None
+}
+
+/// Where a loose ref lives: HEAD and the per-worktree namespaces in the
+/// checkout's own git dir, everything else in the shared common dir.
+fn ref_dir<'a>(repo: &'a Repo, name: &str) -> &'a Path {
+    let per_worktree = name == "HEAD"
+        || ["refs/bisect/", "refs/worktree/", "refs/rewritten/"].iter().any(|p| name.starts_with(p));
+    if per_worktree { &repo.git_dir } else { &repo.common_dir }
+}
+
+fn read_packed_ref(git_dir: &Path, name: &str) -> Option<String> {
+    packed_refs(git_dir).into_iter().find(|r| r.0 == name).map(|r| r.1)
+}
+
+/// `(ref name, sha, peeled sha)` for every entry in `packed-refs`. Annotated
+/// tags point at a tag object; the `^<sha>` line git writes after them names
+/// the commit it peels to.
+fn packed_refs(git_dir: &Path) -> Vec<(String, String, Option<String>)> {
+    let Ok(packed) = fs::read_to_string(git_dir.join("packed-refs")) else { return Vec::new() };
+    let mut refs: Vec<(String, String, Option<String>)> = Vec::new();
+    for line in packed.lines() {
+        if let Some(peeled) = line.strip_prefix('^') {
+            if let Some(last) = refs.last_mut() {
+                last.2 = Some(peeled.to_string());
+            }
+        } else if let Some((sha, name)) = line.split_once(' ')
+            && !line.starts_with('#') {
+                refs.push((name.to_string(), sha.to_string(), None));
+            }
+    }
+    refs
+}
+
+/// Loose refs under `<git_dir>/<prefix>` as `(ref name, sha)`, recursing into
+/// namespaced names (`refs/tags/release/v1`). Symbolic refs such as
+/// `refs/remotes/origin/HEAD` are skipped.
+fn loose_refs(git_dir: &Path, prefix: &str) -> Vec<(String, String)> {
+    let mut out = Vec::new();
+    let Ok(entries) = fs::read_dir(git_dir.join(prefix)) else { return out };
+    for e in entries.flatten() {
+        let Some(file_name) = e.file_name().to_str().map(String::from) else { continue };
+        let name = format!("{}/{}", prefix, file_name);
+        if e.path().is_dir() {
+            out.extend(loose_refs(git_dir, &name));
+        } else if let Ok(content) = fs::read_to_string(e.path())
+            && !content.starts_with("ref: ") {
+                out.push((name, content.trim().to_string()));
+            }
+    }
+    out
+}
+
+/// Names a detached HEAD: a tag pointing at the commit (`v1.2.8`), else a
+/// remote branch (`origin/main`), else `detached@<short sha>`. Tags are
+/// matched by the commit they peel to in `packed-refs`. A loose annotated tag
+/// points at a compressed tag object instead, so when loose tags exist and
+/// none matched directly, git is asked which tags point at the commit; its
+/// answer is cached in the session cache keyed by HEAD's SHA.
+fn describe_detached(repo: &Repo, sha: &str, transcript_path: &str) -> String {
+    let packed_all = packed_refs(&repo.common_dir);
+    let mut candidates: Vec<String> = Vec::new();
+    for prefix in ["refs/tags", "refs/remotes"] {
+        let loose = loose_refs(&repo.common_dir, prefix);
+        let packed = packed_all
+            .iter()
+            .filter(|(name, _, _)| name.starts_with(prefix) && !loose.iter().any(|(l, _)| l == name))
+            .map(|(name, sha, peeled)| (name.clone(), peeled.clone().unwrap_or_else(|| sha.clone())));
+        let mut names: Vec<String> = loose
+            .iter()
+            .cloned()
+            .chain(packed)
+            .filter(|(_, target)| target == sha)
+            .map(|(name, _)| name)
+            .collect();
+        names.sort();
+        candidates.extend(names.into_iter().filter_map(|n| {
+            n.strip_prefix(prefix).and_then(|n| n.strip_prefix('/')).map(String::from)
+        }));
+        if candidates.is_empty() && prefix == "refs/tags" && !loose.is_empty() {
+            candidates.extend(tag_pointing_at(repo, sha, transcript_path));
+        }
+        if !candidates.is_empty() {
+            break;
+        }
+    }
+    match candidates.into_iter().next() {
+        Some(name) => name,
+        None => format!("detached@{}", sha.get(..7).unwrap_or(sha)),
+    }
+}
+
+/// The first tag `git tag --points-at` lists for `sha`. Cached, no tag
+/// included, so a bisect stepping through untagged commits runs git once
+/// per commit.
+fn tag_pointing_at(repo: &Repo, sha: &str, transcript_path: &str) -> Option<String> {
+    let cached = cache::load_raw(transcript_path);
+    if let Some(t) = cached.as_ref().and_then(|c| c.git.head_tag.as_ref())
+        && t.sha == sha {
+            return t.tag.clone();
         }
-        None => Some("detached".to_string()),
+
+    let tag = run_git(repo.run_dir(), &["tag", "--points-at", sha])
+        .and_then(|out| out.lines().next().map(String::from));
+
+    cache::update(transcript_path, |c| c.git.head_tag = Some(cache::HeadTagCache { sha: sha.to_string(), tag: tag.clone() }));
+
+    tag
+}
+
+/// The remote-tracking ref the current branch is configured to follow, from
+/// `branch.<name>.remote` and `branch.<name>.merge`. A remote of `.` tracks
+/// another local branch.
+fn upstream_ref(config: &[ConfigEntry], branch: &str) -> Option<String> {
+    let remote = config_get(config, "branch", Some(branch), "remote")?;
+    let merge = config_get(config, "branch", Some(branch), "merge")?;
+    if remote == "." {
+        return Some(merge.to_string());
     }
+    let name = merge.strip_prefix("refs/heads/").unwrap_or(merge);
+    Some(format!("refs/remotes/{}/{}", remote, name))
+}
+
+/// Commits on the current branch missing from its upstream, and vice versa.
+pub struct AheadBehind {
+    pub ahead: usize,
+    pub behind: usize,
+    /// The configured upstream ref no longer exists — typically the remote
+    /// branch was deleted after its PR merged and a fetch pruned it.
+    pub gone: bool,
+}
+
+/// Counts commits between the current branch and its upstream. Both tips are
+/// resolved from files; git is only spawned to walk history when they differ,
+/// and the result is cached in the session cache keyed on the two SHAs.
+pub fn get_ahead_behind(cwd: &str, transcript_path: &str) -> Option<AheadBehind> {
+    let repo = find_repo(cwd)?;
+    let branch = head_branch(&repo.git_dir)?;
+    let config = read_config(&repo);
+    let upstream = upstream_ref(&config, &branch)?;
+    let local_sha = resolve_ref(&repo, &format!("refs/heads/{}", branch))?;
+    let Some(upstream_sha) = resolve_ref(&repo, &upstream) else {
+        // A missing local upstream (`remote = .`) is a misconfiguration, not
+        // a pruned remote branch.
+        let gone = upstream.starts_with("refs/remotes/");
+        return gone.then_some(AheadBehind { ahead: 0, behind: 0, gone });
+    };
+    if local_sha == upstream_sha {
+        return Some(AheadBehind { ahead: 0, behind: 0, gone: false });
+    }
+
+    let cached = cache::load_raw(transcript_path);
+    if let Some(ab) = cached.as_ref().and_then(|c| c.git.ahead_behind.as_ref())
+        && ab.local == local_sha
+        && ab.upstream == upstream_sha {
+            return Some(AheadBehind { ahead: ab.ahead, behind: ab.behind, gone: false });
+        }
+
+    let range = format!("{}...{}", local_sha, upstream_sha);
+    let output = run_git(repo.run_dir(), &["rev-list", "--left-right", "--count", &range])?;
+    let (ahead, behind) = output.trim().split_once('\t')?;
+    let (ahead, behind) = (ahead.parse().ok()?, behind.parse().ok()?);
+
+    cache::update(transcript_path, |c| c.git.ahead_behind = Some(cache::AheadBehindCache { local: local_sha, upstream: upstream_sha, ahead, behind }));
+
+    Some(AheadBehind { ahead, behind, gone: false })
+}
+
+/// Number of stash entries, for the `stash` field: one reflog line per entry
+/// in `logs/refs/stash` (shared by all worktrees). None when there are none.
+pub fn get_stash_count(cwd: &str) -> Option<usize> {
+    let repo = find_repo(cwd)?;
+    let log = fs::read_to_string(repo.common_dir.join("logs/refs/stash")).ok()?;
+    let count = log.lines().filter(|l| !l.trim().is_empty()).count();
+    (count > 0).then_some(count)
+}
+
+/// HEAD's subject line and committer time (Unix seconds).
+pub struct CommitInfo {
+    pub subject: String,
+    pub time: i64,
+}
+
+/// Summarizes HEAD for the `last-commit` and `commit-age` fields. Commits are
+/// immutable, so the session cache keeps the last result keyed by HEAD's SHA
+/// and git is only consulted when HEAD moves.
+pub fn get_last_commit(cwd: &str, transcript_path: &str) -> Option<CommitInfo> {
+    let repo = find_repo(cwd)?;
+    let sha = resolve_ref(&repo, "HEAD")?;
+
+    let cached = cache::load_raw(transcript_path);
+    if let Some(lc) = cached.as_ref().and_then(|c| c.git.last_commit.as_ref())
+        && lc.sha == sha {
+            return Some(CommitInfo { subject: lc.subject.clone(), time: lc.time });
+        }
+
+    let output = run_git(repo.run_dir(), &["log", "-1", "--no-show-signature", "--format=%ct %s", &sha])?;
+    let (time, subject) = output.trim_end().split_once(' ')?;
+    let info = CommitInfo { subject: subject.to_string(), time: time.parse().ok()? };
+
+    cache::update(transcript_path, |c| c.git.last_commit = Some(cache::LastCommitCache { sha, subject: info.subject.clone(), time: info.time }));
+
+    Some(info)
 }
@@ -4,9 +4,12 @@ mod config;
 mod diff;
 mod fsutil;
 mod git;
+mod path;
 mod process;
+mod shell;
 mod tokens;
 mod usage;
+mod vcs;
 
 use colors::{COLOR_RESET, detect_flavor, resolve_colors};
 use std::io;
@@ -26,14 +29,92 @@ fn link_wrap(text: &str, url: &Option<String>) -> String {
     }
 }
 
+/// `+staged ~modified ?untracked !conflicted`, omitting zero counts.
+fn format_git_status(s: &git::GitStatus) -> String {
+    [('+', s.staged), ('~', s.modified), ('?', s.untracked), ('!', s.conflicted)]
+        .iter()
+        .filter(|(_, n)| *n > 0)
+        .map(|(sym, n)| format!("{}{}", sym, n))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `↑ahead ↓behind`, omitting a zero side, or `⚠ gone` when the upstream
+/// branch was deleted.
+fn format_ahead_behind(ab: &git::AheadBehind) -> String {
+    if ab.gone {
+        return "⚠ gone".to_string();
+    }
+    [('↑', ab.ahead), ('↓', ab.behind)]
+        .iter()
+        .filter(|(_, n)| *n > 0)
+        .map(|(sym, n)| format!("{}{}", sym, n))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `main +120 -30 · 3 agents +400 -12`, or None when no subagent edited a
+/// counted file.
+fn format_agents(agents: &[diff::AgentDiff]) -> Option<String> {
+    let (main, subs) = agents.split_first()?;
+    if subs.is_empty() {
+        return None;
+    }
+    let added: usize = subs.iter().map(|a| a.added).sum();
+    let removed: usize = subs.iter().map(|a| a.removed).sum();
+    let noun = if subs.len() == 1 { "agent" } else { "agents" };
+    Some(format!("main +{} -{} · {} {} +{} -{}", main.added, main.removed, subs.len(), noun, added, removed))
+}
+
+/// Longest commit subject `last-commit` shows before eliding.
+const SUBJECT_MAX_CHARS: usize = 40;
+
+fn truncate_subject(subject: &str) -> String {
+    if subject.chars().count() <= SUBJECT_MAX_CHARS {
+        return subject.to_string();
+    }
+    let head: String = subject.chars().take(SUBJECT_MAX_CHARS - 1).collect();
+    format!("{}…", head.trim_end())
+}
+
+/// Compact age of a Unix timestamp: `now`, `12m`, `3h`, `5d`, `2w`, `4mo`, `1y`.
+fn format_age(time: i64) -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let secs = (now - time).max(0);
+    match secs {
+        s if s < 60 => "now".to_string(),
+        s if s < 3600 => format!("{}m", s / 60),
+        s if s < 86400 => format!("{}h", s / 3600),
+        s if s < 14 * 86400 => format!("{}d", s / 86400),
+        s if s < 60 * 86400 => format!("{}w", s / (7 * 86400)),
+        s if s < 365 * 86400 => format!("{}mo", s / (30 * 86400)),
+        s => format!("{}y", s / (365 * 86400)),
+    }
+}
+
 struct RenderContext<'a> {
     colors: &'a colors::Colors,
     dir_name: &'a str,
-    remote_url: &'a Option<String>,
-    git_branch: &'a str,
+    path: &'a Option<String>,
+    vcs: &'a Option<vcs::VcsInfo>,
+    git_status: &'a Option<git::GitStatus>,
+    ahead_behind: &'a Option<git::AheadBehind>,
+    git_state: &'a Option<String>,
+    worktree: &'a Option<String>,
+    last_commit: &'a Option<git::CommitInfo>,
+    stash: Option<usize>,
+    submodule: &'a Option<String>,
     model_name: &'a str,
     added: usize,
     removed: usize,
+    excluded: usize,
+    drifted: usize,
+    agents: &'a [diff::AgentDiff],
+    files: &'a diff::FileCounts,
+    today: Option<(usize, usize)>,
     token_info: &'a Option<String>,
     token_percent_info: &'a Option<String>,
     stats: &'a Option<process::ClaudeStats>,
@@ -45,9 +126,30 @@ struct RenderContext<'a> {
 fn render_field(field: &str, ctx: &RenderContext) -> Option<String> {
     let c = ctx.colors;
     match field {
-        "dir" => Some(format!("{}{}{}", c.dir, link_wrap(ctx.dir_name, ctx.remote_url), COLOR_RESET)),
-        "branch" => Some(format!("{}{}{}", c.branch, ctx.git_branch, COLOR_RESET)),
-        "diff" => Some(format!("{}+{}{} {}-{}{}", c.added, ctx.added, COLOR_RESET, c.removed, ctx.removed, COLOR_RESET)),
+        "dir" => Some(format!("{}{}{}", c.dir, link_wrap(ctx.dir_name, &ctx.vcs.as_ref().and_then(|g| g.remote_url.clone())), COLOR_RESET)),
+        "path" => ctx.path.as_ref().map(|p| format!("{}{}{}", c.path, p, COLOR_RESET)),
+        "branch" => Some(format!("{}{}{}", c.branch, ctx.vcs.as_ref().map(|g| link_wrap(&g.branch, &g.branch_url)).unwrap_or_default(), COLOR_RESET)),
+        "git-status" => ctx.git_status.as_ref().filter(|s| !s.is_clean()).map(|s| format!("{}{}{}", c.git_status, format_git_status(s), COLOR_RESET)),
+        "ahead-behind" => ctx.ahead_behind.as_ref().filter(|ab| ab.ahead > 0 || ab.behind > 0 || ab.gone).map(|ab| format!("{}{}{}", c.ahead_behind, format_ahead_behind(ab), COLOR_RESET)),
+        "git-state" => ctx.git_state.as_ref().map(|s| format!("{}{}{}", c.git_state, s, COLOR_RESET)),
+        "worktree" => ctx.worktree.as_ref().map(|w| format!("{}wt:{}{}", c.worktree, w, COLOR_RESET)),
+        "submodule" => ctx.submodule.as_ref().map(|s| format!("{}sub:{}{}", c.submodule, s, COLOR_RESET)),
+        "last-commit" => ctx.last_commit.as_ref().map(|lc| format!("{}{}{}", c.last_commit, truncate_subject(&lc.subject), COLOR_RESET)),
+        "commit-age" => ctx.last_commit.as_ref().map(|lc| format!("{}{}{}", c.commit_age, format_age(lc.time), COLOR_RESET)),
+        "stash" => ctx.stash.map(|n| format!("{}stash:{}{}", c.stash, n, COLOR_RESET)),
+        "diff" => {
+            let text = format!("{}+{}{} {}-{}{}", c.added, ctx.added, COLOR_RESET, c.removed, ctx.removed, COLOR_RESET);
+            Some(link_wrap(&text, &ctx.vcs.as_ref().and_then(|g| g.compare_url.clone())))
+        }
+        "diff-excluded" => (ctx.excluded > 0).then(|| format!("{}excluded:{}{}", c.diff_excluded, ctx.excluded, COLOR_RESET)),
+        "files" => Some(format!("{}{} {}{}", c.files, ctx.files.changed, if ctx.files.changed == 1 { "file" } else { "files" }, COLOR_RESET)),
+        "files-new" => (ctx.files.created > 0).then(|| format!("{}{} new{}", c.files_new, ctx.files.created, COLOR_RESET)),
+        "files-deleted" => (ctx.files.deleted > 0).then(|| format!("{}{} deleted{}", c.files_deleted, ctx.files.deleted, COLOR_RESET)),
+        "files-touched-outside-repo" => (ctx.files.outside_repo > 0)
+            .then(|| format!("{}{} outside repo{}", c.files_outside_repo, ctx.files.outside_repo, COLOR_RESET)),
+        "diff-agents" => format_agents(ctx.agents).map(|t| format!("{}{}{}", c.diff_agents, t, COLOR_RESET)),
+        "diff-today" => ctx.today.map(|(a, r)| format!("{}today +{} -{}{}", c.diff_today, a, r, COLOR_RESET)),
+        "diff-drift" => (ctx.drifted > 0).then(|| format!("{}drift:{}{}", c.diff_drift, ctx.drifted, COLOR_RESET)),
         "model" => Some(format!("{}{}{}", c.model, ctx.model_name, COLOR_RESET)),
         "tokens" => ctx.token_info.as_ref().map(|t| format!("{}{}{}", c.tokens, t, COLOR_RESET)),
         "tokens-percent" => ctx.token_percent_info.as_ref().map(|t| format!("{}{}{}", c.tokens_percent, t, COLOR_RESET)),
@@ -60,6 +162,192 @@ fn render_field(field: &str, ctx: &RenderContext) -> Option<String> {
     }
 }
 
+/// `--diff-files`: one line per touched file, most churn first, with paths
+/// under the cwd shown relative to it.
+fn print_diff_files(input: &tokens::Input, cfg: &config::Config) {
+    let filter = diff::DiffFilter::new(&cfg.diff, &input.cwd);
+    let files = diff::file_diffs(&input.transcript_path, &filter);
+    let counts: Vec<String> = files.iter().map(|f| format!("+{} -{}", f.added, f.removed)).collect();
+    let width = counts.iter().map(String::len).max().unwrap_or(0);
+    let relative = |path: &str| {
+        std::path::Path::new(path)
+            .strip_prefix(&input.cwd)
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| path.to_string())
+    };
+    for (f, count) in files.iter().zip(&counts) {
+        let mut line = format!("{:<width$}  {}", count, relative(&f.path));
+        if f.created {
+            line.push_str("  (new)");
+        }
+        if f.subagent_only {
+            line.push_str("  (subagent)");
+        }
+        if f.excluded {
+            line.push_str("  (excluded)");
+        }
+        if f.drifted {
+            line.push_str("  (drifted)");
+        }
+        if f.deleted {
+            line.push_str("  (deleted)");
+        }
+        if let Some(from) = &f.renamed_from {
+            line.push_str(&format!("  (moved from {})", relative(from)));
+        }
+        println!("{}", line);
+    }
+}
+
+/// `--diff-agents`: the main transcript's share of the session diff, then
+/// each subagent transcript's, named by file stem.
+fn print_diff_agents(input: &tokens::Input, cfg: &config::Config) {
+    let filter = diff::DiffFilter::new(&cfg.diff, &input.cwd);
+    let agents = diff::agent_diffs(&input.transcript_path, &filter);
+    let counts: Vec<String> = agents.iter().map(|a| format!("+{} -{}", a.added, a.removed)).collect();
+    let names: Vec<String> = agents.iter().enumerate()
+        .map(|(i, a)| match std::path::Path::new(&a.transcript).file_stem() {
+            Some(stem) if i > 0 => stem.to_string_lossy().into_owned(),
+            _ => "main".to_string(),
+        })
+        .collect();
+    let count_width = counts.iter().map(String::len).max().unwrap_or(0);
+    let name_width = names.iter().map(String::len).max().unwrap_or(0);
+    for ((a, count), name) in agents.iter().zip(&counts).zip(&names) {
+        let noun = if a.files == 1 { "file" } else { "files" };
+        println!("{:<count_width$}  {:<name_width$}  {} {}", count, name, a.files, noun);
+    }
+}
+
+/// `--diff-summary [--since today|week]`: the net change every session in
+/// the current directory's repository made over the period, per file with
+/// paths relative to the repository root, then the total.
+fn print_diff_summary(args: &[String]) {
+    let period = match args.iter().position(|a| a == "--since") {
+        Some(i) => args.get(i + 1).map(String::as_str).unwrap_or(""),
+        None => "today",
+    };
+    let cfg = config::load_config();
+    let Some((since, since_ms)) = usage::period_start(period, cfg.track_usage.timezone.as_deref()) else {
+        eprintln!("usage: statusline --diff-summary [--since today|week]");
+        std::process::exit(2);
+    };
+    let cwd = std::env::current_dir().map(|d| d.to_string_lossy().into_owned()).unwrap_or_default();
+    let filter = diff::DiffFilter::new(&cfg.diff, &cwd);
+    let summary = diff::project_diff(&cwd, &since, since_ms, &filter);
+
+    let counts: Vec<String> = summary.files.iter().map(|(_, a, r)| format!("+{} -{}", a, r)).collect();
+    let total = format!("+{} -{}", summary.added, summary.removed);
+    let width = counts.iter().chain(std::iter::once(&total)).map(String::len).max().unwrap_or(0);
+    for ((path, _, _), count) in summary.files.iter().zip(&counts) {
+        let shown = std::path::Path::new(path).strip_prefix(&summary.root).map(|p| p.to_string_lossy().into_owned());
+        println!("{:<width$}  {}", count, shown.unwrap_or_else(|_| path.clone()));
+    }
+    let files = summary.files.len();
+    let sessions = summary.sessions;
+    println!(
+        "{:<width$}  {} {} in {} {} {}",
+        total,
+        files,
+        if files == 1 { "file" } else { "files" },
+        sessions,
+        if sessions == 1 { "session" } else { "sessions" },
+        if period == "week" { "this week" } else { "today" },
+    );
+}
+
+/// `1.2 MB`-style size for `cache status`.
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        b if b < 1024 => format!("{} B", b),
+        b if b < 1024 * 1024 => format!("{:.1} KB", b as f64 / 1024.0),
+        b => format!("{:.1} MB", b as f64 / (1024.0 * 1024.0)),
+    }
+}
+
+/// `statusline cache status` and `statusline cache clear [--session ID|--usage|--all]`.
+/// A bare `clear` drops every session and project cache but keeps the usage
+/// cache, whose totals can only be rebuilt from transcripts still on disk.
+fn cache_command(args: &[String]) {
+    let cfg = config::load_config();
+    let usage_path = cfg.track_usage.output_path.as_deref();
+    match args.first().map(String::as_str) {
+        Some("status") => {
+            let sessions = cache::sessions();
+            match cache::cache_dir() {
+                Some(dir) => println!("Session caches in {}: {}", dir.display(), sessions.len()),
+                None => println!("Session caches: unavailable (no usable cache directory)"),
+            }
+            let offsets: Vec<String> = sessions.iter().map(|s| format!("offset {}", s.byte_offset)).collect();
+            let files: Vec<String> = sessions.iter()
+                .map(|s| format!("{} {}", s.files, if s.files == 1 { "file" } else { "files" }))
+                .collect();
+            let pids: Vec<String> = sessions.iter()
+                .map(|s| s.claude_pid.map_or("pid -".to_string(), |p| format!("pid {}", p)))
+                .collect();
+            let width = |v: &[String]| v.iter().map(String::len).max().unwrap_or(0);
+            let (id_w, off_w, files_w, pid_w) = (
+                sessions.iter().map(|s| s.id.len()).max().unwrap_or(0),
+                width(&offsets),
+                width(&files),
+                width(&pids),
+            );
+            for (((s, offset), files), pid) in sessions.iter().zip(&offsets).zip(&files).zip(&pids) {
+                let transcript = if s.transcript.is_empty() { "?" } else { &s.transcript };
+                println!("  {:<id_w$}  {:<off_w$}  {:<files_w$}  {:<pid_w$}  {}", s.id, offset, files, pid, transcript);
+            }
+            let projects = cache::project_caches();
+            println!(
+                "Project caches: {}, {}",
+                projects.len(),
+                format_size(projects.iter().map(|(_, size)| size).sum()),
+            );
+            match usage::cache_info(usage_path) {
+                Some((path, size, transcripts)) => println!(
+                    "Usage cache {}: {}, {} {}",
+                    path.display(),
+                    format_size(size),
+                    transcripts,
+                    if transcripts == 1 { "transcript" } else { "transcripts" },
+                ),
+                None => println!("Usage cache: none"),
+            }
+        }
+        Some("clear") => {
+            let opts = &args[1..];
+            let all = opts.iter().any(|a| a == "--all");
+            let usage_only = opts.iter().any(|a| a == "--usage");
+            let session = opts.iter().position(|a| a == "--session").map(|i| opts.get(i + 1));
+            if session == Some(None) || (session.is_some() && (all || usage_only)) {
+                eprintln!("usage: statusline cache clear [--session ID|--usage|--all]");
+                std::process::exit(2);
+            }
+            if !usage_only {
+                let n = cache::clear_sessions(session.flatten().map(String::as_str));
+                println!("Removed {} session {}", n, if n == 1 { "cache" } else { "caches" });
+            }
+            if session.is_none() && !usage_only {
+                let n = cache::clear_projects();
+                println!("Removed {} project {}", n, if n == 1 { "cache" } else { "caches" });
+            }
+            if all || usage_only {
+                match usage::clear_cache(usage_path) {
+                    Ok(true) => println!("Cleared usage cache"),
+                    Ok(false) => println!("No usage cache"),
+                    Err(e) => {
+                        eprintln!("statusline: can't clear usage cache: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+        _ => {
+            eprintln!("usage: statusline cache status|clear [--session ID|--usage|--all]");
+            std::process::exit(2);
+        }
+    }
+}
+
 fn main() {
     let args: Vec<String> = std::env::args().collect();
 
@@ -76,23 +364,89 @@ fn main() {
         return;
     }
 
+    if args.get(1).is_some_and(|a| a == "cache") {
+        cache_command(&args[2..]);
+        return;
+    }
+
+    if let Some(i) = args.iter().position(|a| a == "--export-patch") {
+        let Some(transcript) = args.get(i + 1) else {
+            eprintln!("usage: statusline --export-patch <transcript.jsonl>");
+            std::process::exit(2);
+        };
+        // Relative to the session's repository, wherever this runs from; the
+        // current directory stands in for transcripts that record no cwd.
+        let cwd = diff::transcript_cwd(std::path::Path::new(transcript))
+            .or_else(|| std::env::current_dir().ok().map(|d| d.to_string_lossy().into_owned()));
+        let root = cwd.and_then(|d| git::get_repo_root(&d));
+        print!("{}", diff::export_patch(transcript, root.as_deref()));
+        return;
+    }
+
+    if args.iter().any(|a| a == "--diff-summary") {
+        print_diff_summary(&args);
+        return;
+    }
+
     let input: tokens::Input = match serde_json::from_reader(io::stdin()) {
         Ok(i) => i,
         Err(_) => std::process::exit(1),
     };
 
     let cfg = config::load_config();
+    if args.iter().any(|a| a == "--diff-files") {
+        print_diff_files(&input, &cfg);
+        return;
+    }
+    if args.iter().any(|a| a == "--diff-agents") {
+        print_diff_agents(&input, &cfg);
+        return;
+    }
     let flavor = detect_flavor();
     let colors = resolve_colors(flavor, &cfg.colors);
 
-    let needs_git = cfg.has_field("dir") || cfg.has_field("branch");
-    let needs_diff = cfg.has_field("diff");
+    let needs_vcs = cfg.has_field("dir") || cfg.has_field("branch") || cfg.has_field("diff");
+    let needs_diff = ["diff", "diff-excluded", "diff-drift", "diff-agents", "files", "files-new", "files-deleted", "files-touched-outside-repo"]
+        .iter()
+        .any(|f| cfg.has_field(f));
     let needs_stats = cfg.has_field("cpu") || cfg.has_field("ram");
 
     let dir_name = get_dir_name(&input.cwd);
-    let (git_branch, remote_url) = if needs_git { git::get_git_info(&input.cwd) } else { (String::new(), None) };
+    let path = if cfg.has_field("path") { Some(path::get_path(&input.cwd, &cfg.path)) } else { None };
+    let vcs_info = if needs_vcs { Some(vcs::get_vcs_info(&input.cwd, &input.transcript_path, &cfg.git, cfg.has_field("branch"))) } else { None };
+    let git_status = if cfg.has_field("git-status") { git::get_git_status(&input.cwd, &input.transcript_path) } else { None };
+    let ahead_behind = if cfg.has_field("ahead-behind") { git::get_ahead_behind(&input.cwd, &input.transcript_path) } else { None };
+    let git_state = if cfg.has_field("git-state") { git::get_git_state(&input.cwd) } else { None };
+    let worktree = if cfg.has_field("worktree") { git::get_worktree(&input.cwd) } else { None };
+    let submodule = if cfg.has_field("submodule") { git::get_submodule(&input.cwd) } else { None };
+    let last_commit = if cfg.has_field("last-commit") || cfg.has_field("commit-age") {
+        git::get_last_commit(&input.cwd, &input.transcript_path)
+    } else {
+        None
+    };
+    let stash = if cfg.has_field("stash") { git::get_stash_count(&input.cwd) } else { None };
     let model_name = input.model.display_name.split('(').next().unwrap_or(&input.model.display_name).trim().to_string();
-    let (added, removed) = if needs_diff { diff::calculate_net_diff(&input.transcript_path) } else { (0, 0) };
+    let net_diff = if needs_diff {
+        // Recorded whatever the mode, so switching to git-session-base mid-session
+        // still measures from where the session began.
+        let session_base = git::get_session_base(&input.cwd, &input.transcript_path);
+        let filter = diff::DiffFilter::new(&cfg.diff, &input.cwd);
+        match cfg.diff.mode.as_str() {
+            "git-head" => diff::calculate_git_diff(&input.cwd, &input.transcript_path, &filter, None),
+            "git-session-base" => diff::calculate_git_diff(&input.cwd, &input.transcript_path, &filter, session_base.as_deref()),
+            _ => diff::calculate_net_diff(&input.transcript_path, &filter),
+        }
+    } else {
+        diff::NetDiff::default()
+    };
+    let today = if cfg.has_field("diff-today") {
+        usage::period_start("today", cfg.track_usage.timezone.as_deref()).map(|(since, since_ms)| {
+            let filter = diff::DiffFilter::new(&cfg.diff, &input.cwd);
+            diff::project_totals(&input.cwd, &input.transcript_path, &since, since_ms, &filter)
+        })
+    } else {
+        None
+    };
     let token_info = if cfg.has_field("tokens") { tokens::get_token_info(&input) } else { None };
     let token_percent_info = if cfg.has_field("tokens-percent") { tokens::get_token_percent_info(&input) } else { None };
     let stats = if needs_stats { process::get_claude_stats(&input.transcript_path) } else { None };
@@ -103,11 +457,23 @@ fn main() {
     let ctx = RenderContext {
         colors: &colors,
         dir_name: &dir_name,
-        remote_url: &remote_url,
-        git_branch: &git_branch,
+        path: &path,
+        vcs: &vcs_info,
+        git_status: &git_status,
+        ahead_behind: &ahead_behind,
+        git_state: &git_state,
+        worktree: &worktree,
+        last_commit: &last_commit,
+        stash,
+        submodule: &submodule,
         model_name: &model_name,
-        added,
-        removed,
+        added: net_diff.added,
+        removed: net_diff.removed,
+        excluded: net_diff.excluded,
+        drifted: net_diff.drifted,
+        agents: &net_diff.agents,
+        files: &net_diff.files,
+        today,
         token_info: &token_info,
         token_percent_info: &token_percent_info,
         stats: &stats,
@@ -127,6 +493,8 @@ fn main() {
         }
     }
 
+    cache::sweep(cfg.cache.max_age_days);
+
     if cfg.track_usage.enabled {
         usage::update(
             cfg.track_usage.output_path.as_deref(),
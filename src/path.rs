@@ -0,0 +1,79 @@
+use crate::config::PathConfig;
+use crate::git;
+use std::path::Path;
+
+/// The cwd as shown by the `path` field: relative to the repository's top
+/// level and prefixed with its name (`monorepo/packages/api/src`), or with
+/// `$HOME` shortened to `~` outside a repository. `cfg` then shortens it.
+pub fn get_path(cwd: &str, cfg: &PathConfig) -> String {
+    let components = match git::get_repo_root(cwd) {
+        Some(root) => {
+            let name = root.file_name().and_then(|n| n.to_str()).unwrap_or("/");
+            let rel = Path::new(cwd).strip_prefix(&root).unwrap_or(Path::new(""));
+            std::iter::once(name.to_string()).chain(components_of(rel)).collect()
+        }
+        None => home_relative(cwd),
+    };
+    shorten(components, cfg)
+}
+
+fn components_of(p: &Path) -> impl Iterator<Item = String> + '_ {
+    p.components().filter_map(|c| match c {
+        std::path::Component::Normal(s) => s.to_str().map(String::from),
+        _ => None,
+    })
+}
+
+fn home_relative(cwd: &str) -> Vec<String> {
+    if let Ok(home) = std::env::var("HOME")
+        && let Ok(rel) = Path::new(cwd).strip_prefix(&home) {
+            return std::iter::once("~".to_string()).chain(components_of(rel)).collect();
+        }
+    let mut out: Vec<String> = components_of(Path::new(cwd)).collect();
+    if let Some(first) = out.first_mut() {
+        first.insert(0, '/');
+    }
+    out
+}
+
+fn shorten(mut components: Vec<String>, cfg: &PathConfig) -> String {
+    let abbreviate_before = components.len().saturating_sub(cfg.keep);
+    match cfg.style.as_str() {
+        "fish" => {
+            for c in components.iter_mut().take(abbreviate_before) {
+                *c = abbreviate(c);
+            }
+        }
+        "last" if abbreviate_before > 0 => {
+            components.drain(..abbreviate_before);
+            components.insert(0, "…".to_string());
+        }
+        _ => {}
+    }
+    let joined = components.join("/");
+    match cfg.max_width {
+        Some(max) if joined.chars().count() > max => elide_left(&joined, max),
+        _ => joined,
+    }
+}
+
+/// First character of a component, keeping a leading `.` so `.config`
+/// becomes `.c` rather than an ambiguous `.`.
+fn abbreviate(component: &str) -> String {
+    let mut chars = component.chars();
+    match chars.next() {
+        Some('.') => chars.next().map(|c| format!(".{}", c)).unwrap_or_else(|| ".".to_string()),
+        Some(c) => c.to_string(),
+        None => String::new(),
+    }
+}
+
+/// Keeps the rightmost `max - 1` characters behind a leading `…`.
+fn elide_left(s: &str, max: usize) -> String {
+    if max == 0 {
+        return String::new();
+    }
+    let count = s.chars().count();
+    let tail: String = s.chars().skip(count + 1 - max).collect();
+    format!("…{}", tail)
+}
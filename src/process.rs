@@ -1,6 +1,8 @@
 use crate::cache;
 use std::collections::HashMap;
-use std::process::Command;
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
 
 pub struct ClaudeStats {
     pub cpu: String,
@@ -73,12 +75,11 @@ fn get_stats_for_pid(pid: u32) -> Option<ClaudeStats> {
 }
 
 pub fn get_claude_stats(transcript_path: &str) -> Option<ClaudeStats> {
-    let cache_path = cache::get_cache_path(transcript_path);
-    let cached = cache::load_raw(&cache_path);
+    let cached = cache::load_raw(transcript_path);
 
     // Try cached PID first — single ps call validates + gets stats
     if let Some(ref c) = cached
-        && let Some(pid) = c.claude_pid
+        && let Some(pid) = c.process.claude_pid
             && let Some(stats) = get_stats_for_pid(pid) {
                 return Some(stats);
             }
@@ -86,11 +87,42 @@ pub fn get_claude_stats(transcript_path: &str) -> Option<ClaudeStats> {
     // Cache miss or stale PID: walk the process tree
     let pid = find_claude_pid()?;
 
-    // Update cache if readable, otherwise just skip caching
-    if let Some(mut c) = cache::load_raw(&cache_path) {
-        c.claude_pid = Some(pid);
-        cache::save(&cache_path, &c);
-    }
+    cache::update(transcript_path, |c| c.process.claude_pid = Some(pid));
 
     get_stats_for_pid(pid)
 }
+
+/// Runs `cmd` and returns its stdout if it exits successfully within
+/// `timeout`; otherwise kills it and returns None. For helpers (git, jj, hg)
+/// whose worst case — a huge tree, a cold disk — must not stall a render.
+pub fn output_with_timeout(cmd: &mut Command, timeout: Duration) -> Option<String> {
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+
+    // Drain stdout on a thread: a large listing would otherwise fill the pipe
+    // and block the child before it can exit.
+    let mut stdout = child.stdout.take()?;
+    let reader = std::thread::spawn(move || {
+        let mut buf = String::new();
+        stdout.read_to_string(&mut buf).ok().map(|_| buf)
+    });
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => break,
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(5)),
+            Ok(Some(_)) => return None,
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    }
+    reader.join().ok()?
+}
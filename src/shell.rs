@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+
+/// A file deletion or rename a Bash command performs, with paths made
+/// absolute. Deleted paths may be globs (`rm *.txt`).
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum FileOp {
+    Delete { path: String, recursive: bool },
+    Rename { from: String, to: String },
+}
+
+/// The `rm`, `mv`, `git rm` and `git mv` operations in a command line run
+/// from `cwd`. Relative paths are skipped when `cwd` is unknown, as are
+/// words that need expansion beyond globs (`$VAR`, `$(...)`). A leading
+/// `cd` or `git -C` moves the directory the commands after it run in.
+pub fn file_ops(command: &str, cwd: Option<&Path>) -> Vec<FileOp> {
+    let mut cwd = cwd.map(Path::to_path_buf);
+    let mut ops = Vec::new();
+    for words in simple_commands(command) {
+        let Some((name, args)) = words.split_first() else { continue };
+        match name.as_str() {
+            "cd" => cwd = args.first().and_then(|d| resolve(cwd.as_deref(), d)).map(PathBuf::from),
+            "rm" => ops.extend(delete_ops(args, cwd.as_deref())),
+            "mv" => ops.extend(rename_ops(args, cwd.as_deref())),
+            "git" => {
+                let mut dir = cwd.clone();
+                let mut rest = args;
+                // Global options before the subcommand; only -C moves anything.
+                while let Some((opt, tail)) = rest.split_first() {
+                    if opt == "-C" {
+                        let Some((d, tail)) = tail.split_first() else { break };
+                        dir = resolve(dir.as_deref(), d).map(PathBuf::from);
+                        rest = tail;
+                    } else if opt == "-c" {
+                        rest = tail.get(1..).unwrap_or_default();
+                    } else if opt.starts_with('-') {
+                        rest = tail;
+                    } else {
+                        break;
+                    }
+                }
+                match rest.split_first() {
+                    // --cached only unstages: the file stays on disk.
+                    Some((sub, args)) if sub == "rm" && !args.iter().any(|a| a == "--cached") => {
+                        ops.extend(delete_ops(args, dir.as_deref()));
+                    }
+                    Some((sub, args)) if sub == "mv" => ops.extend(rename_ops(args, dir.as_deref())),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    ops
+}
+
+fn delete_ops(args: &[String], cwd: Option<&Path>) -> Vec<FileOp> {
+    let (flags, paths) = split_flags(args);
+    let recursive = flags.iter().any(|f| f == "--recursive" || (!f.starts_with("--") && (f.contains('r') || f.contains('R'))));
+    paths.iter()
+        .filter_map(|p| resolve(cwd, p))
+        .map(|path| FileOp::Delete { path, recursive })
+        .collect()
+}
+
+/// `mv SRC DST` or `mv SRC... DIR`. Whether DST is a directory is decided
+/// when the rename is applied, from the disk (see `diff::SessionFiles`).
+fn rename_ops(args: &[String], cwd: Option<&Path>) -> Vec<FileOp> {
+    let (flags, paths) = split_flags(args);
+    // -t DIR puts the destination first; rare enough not to follow.
+    if flags.iter().any(|f| f == "-t" || f.starts_with("--target-directory")) {
+        return Vec::new();
+    }
+    let Some((dst, srcs)) = paths.split_last() else { return Vec::new() };
+    let Some(mut to) = resolve(cwd, dst) else { return Vec::new() };
+    if srcs.len() > 1 || dst.ends_with('/') {
+        to.push('/');
+    }
+    srcs.iter()
+        .filter_map(|s| resolve(cwd, s))
+        .map(|from| FileOp::Rename { from, to: to.clone() })
+        .collect()
+}
+
+/// Leading `-x` options, up to a `--`, and the operands after them.
+fn split_flags(args: &[String]) -> (&[String], &[String]) {
+    let n = args.iter().take_while(|a| a.starts_with('-') && a.len() > 1 && *a != "--").count();
+    let operands = &args[n..];
+    let operands = operands.strip_prefix(&["--".to_string()][..]).unwrap_or(operands);
+    (&args[..n], operands)
+}
+
+/// `word` as an absolute, lexically normalized path. None when it can't be
+/// known without running a shell.
+fn resolve(cwd: Option<&Path>, word: &str) -> Option<String> {
+    if word.contains('$') || word.contains('`') {
+        return None;
+    }
+    let expanded = crate::fsutil::expand_tilde(word);
+    let path = if expanded.is_absolute() { expanded } else { cwd?.join(expanded) };
+    let mut out = PathBuf::new();
+    for c in path.components() {
+        match c {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            c => out.push(c),
+        }
+    }
+    Some(out.to_string_lossy().into_owned())
+}
+
+/// Splits a command line into simple commands of unquoted words. Quotes and
+/// backslashes are honored; `;`, `&`, `|`, newlines, parentheses and
+/// redirections all end a command, so a redirection target never reads as an
+/// operand. Neither does the descriptor before one (`2>/dev/null`, `2>&1`).
+fn simple_commands(line: &str) -> Vec<Vec<String>> {
+    let mut commands = Vec::new();
+    let mut words: Vec<String> = Vec::new();
+    let mut word: Option<String> = None;
+    // Whether `word` holds only unquoted characters, so `"2">x` keeps its 2.
+    let mut bare = true;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                bare = false;
+                let w = word.get_or_insert_default();
+                for q in chars.by_ref() {
+                    if q == '\'' {
+                        break;
+                    }
+                    w.push(q);
+                }
+            }
+            '"' => {
+                bare = false;
+                let w = word.get_or_insert_default();
+                while let Some(q) = chars.next() {
+                    match q {
+                        '"' => break,
+                        '\\' => w.extend(chars.next()),
+                        q => w.push(q),
+                    }
+                }
+            }
+            '\\' => {
+                bare = false;
+                word.get_or_insert_default().extend(chars.next());
+            }
+            ' ' | '\t' => {
+                words.extend(word.take());
+                bare = true;
+            }
+            ';' | '&' | '|' | '\n' | '(' | ')' | '<' | '>' => {
+                if matches!(c, '<' | '>') {
+                    if bare && word.as_deref().is_some_and(|w| w.bytes().all(|b| b.is_ascii_digit())) {
+                        word = None;
+                    }
+                    // `>&2` and `2>&1` name a descriptor, not a background job.
+                    if chars.peek() == Some(&'&') {
+                        chars.next();
+                    }
+                }
+                words.extend(word.take());
+                bare = true;
+                if !words.is_empty() {
+                    commands.push(std::mem::take(&mut words));
+                }
+            }
+            c => word.get_or_insert_default().push(c),
+        }
+    }
+    words.extend(word.take());
+    if !words.is_empty() {
+        commands.push(words);
+    }
+    commands
+}
@@ -296,6 +296,24 @@ fn bucket_date(ts: &str, offset_min: i32) -> Option<NaiveDate> {
         .map(|dt| dt.date())
 }
 
+/// When the current day (`today`) or ISO week (`week`, from Monday) began
+/// in the configured timezone, as a UTC transcript timestamp key
+/// (`YYYY-MM-DDTHH:MM:SS`) and Unix milliseconds. None for any other period.
+pub fn period_start(period: &str, timezone: Option<&str>) -> Option<(String, u128)> {
+    let (offset_min, _) = resolve_timezone(timezone);
+    let now = SystemTime::now().duration_since(std::time::UNIX_EPOCH).ok()?.as_secs() as i64;
+    let local = DateTime::from_timestamp(now, 0)?.naive_utc() + TimeDelta::minutes(offset_min as i64);
+    let day = local.date();
+    let first = match period {
+        "today" => day,
+        "week" => day - TimeDelta::days(day.weekday().num_days_from_monday() as i64),
+        _ => return None,
+    };
+    let start = first.and_hms_opt(0, 0, 0)? - TimeDelta::minutes(offset_min as i64);
+    let ms = u128::try_from(start.and_utc().timestamp_millis()).ok()?;
+    Some((start.format("%Y-%m-%dT%H:%M:%S").to_string(), ms))
+}
+
 fn daily_key(d: NaiveDate) -> String {
     format!("{:04}-{:02}-{:02}", d.year(), d.month(), d.day())
 }
@@ -332,19 +350,11 @@ fn projects_dir() -> Option<PathBuf> {
     std::env::var("HOME").ok().map(|h| PathBuf::from(h).join(".claude/projects"))
 }
 
-fn expand_tilde(p: &str) -> PathBuf {
-    if let Some(rest) = p.strip_prefix("~/")
-        && let Ok(home) = std::env::var("HOME") {
-            return PathBuf::from(home).join(rest);
-        }
-    PathBuf::from(p)
-}
-
 /// Directory holding the three output files. `output_path` always names a
 /// directory (created on demand); default is `~/.claude/usage`.
 fn output_dir(custom: Option<&str>) -> Option<PathBuf> {
     if let Some(p) = custom {
-        return Some(expand_tilde(p));
+        return Some(crate::fsutil::expand_tilde(p));
     }
     std::env::var("HOME").ok().map(|h| PathBuf::from(h).join(".claude/usage"))
 }
@@ -357,7 +367,7 @@ fn sessions_path(custom: Option<&str>) -> Option<PathBuf> {
     output_dir(custom).map(|d| d.join("usage-sessions.json"))
 }
 
-fn list_transcripts() -> Vec<PathBuf> {
+pub fn list_transcripts() -> Vec<PathBuf> {
     let Some(root) = projects_dir() else { return Vec::new() };
     let mut out = Vec::new();
     let Ok(entries) = fs::read_dir(&root) else { return out };
@@ -537,22 +547,6 @@ fn local_offset_minutes() -> Option<i32> {
     None
 }
 
-fn atomic_write(path: &std::path::Path, bytes: &[u8]) -> std::io::Result<()> {
-    if let Some(parent) = path.parent()
-        && !parent.as_os_str().is_empty() {
-            let _ = fs::create_dir_all(parent);
-        }
-    // Per-PID temp name so two processes sharing one output_path (e.g. host +
-    // devcontainer on a bind mount, which hold different cache locks) don't
-    // clobber each other's temp file mid-write.
-    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
-    tmp_name.push(format!(".{}.tmp", std::process::id()));
-    let tmp = path.with_file_name(tmp_name);
-    fs::write(&tmp, bytes)?;
-    fs::rename(&tmp, path)?;
-    Ok(())
-}
-
 fn write_sessions(cache: &Cache, custom_path: Option<&str>, tz_offset: i32, tz_label: &str) -> std::io::Result<()> {
     let Some(path) = sessions_path(custom_path) else { return Ok(()) };
     let out = SessionsOutput {
@@ -561,7 +555,7 @@ fn write_sessions(cache: &Cache, custom_path: Option<&str>, tz_offset: i32, tz_l
         sessions: &cache.sessions,
     };
     let bytes = serde_json::to_vec_pretty(&out).unwrap_or_default();
-    atomic_write(&path, &bytes)
+    crate::fsutil::atomic_write(&path, &bytes)
 }
 
 fn write_summary(cache: &Cache, custom_path: Option<&str>, tz_offset: i32, tz_label: &str) -> std::io::Result<()> {
@@ -578,7 +572,7 @@ fn write_summary(cache: &Cache, custom_path: Option<&str>, tz_offset: i32, tz_la
         monthly: to_vec(&cache.monthly),
     };
     let bytes = serde_json::to_vec_pretty(&out).unwrap_or_default();
-    atomic_write(&path, &bytes)
+    crate::fsutil::atomic_write(&path, &bytes)
 }
 
 /// Upper bound on per-session detail retained in the cache and usage-sessions
@@ -606,6 +600,35 @@ fn prune_sessions(cache: &mut Cache) {
     }
 }
 
+/// The usage cache's path, size in bytes and number of transcripts folded
+/// into it, for `statusline cache status`. None when there's no cache yet.
+pub fn cache_info(custom_path: Option<&str>) -> Option<(PathBuf, u64, usize)> {
+    let path = cache_path(custom_path);
+    let size = fs::metadata(&path).ok()?.len();
+    let files = fs::read_to_string(&path)
+        .ok()
+        .and_then(|s| serde_json::from_str::<Cache>(&s).ok())
+        .map_or(0, |c| c.files.len());
+    Some((path, size, files))
+}
+
+/// Empties the usage cache, so the next update folds every transcript again
+/// from the start. Waits for a running update to release the cache's lock,
+/// and truncates rather than deletes it so an update queued on the lock
+/// starts from the empty cache. The summary and sessions files are left to
+/// be rewritten by that update. False when there was no cache.
+pub fn clear_cache(custom_path: Option<&str>) -> std::io::Result<bool> {
+    let path = cache_path(custom_path);
+    let lock_file = match OpenOptions::new().write(true).open(&path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    lock_file.lock()?;
+    lock_file.set_len(0)?;
+    Ok(true)
+}
+
 pub fn update(custom_path: Option<&str>, tz_spec: Option<&str>) {
     let _ = try_update(custom_path, tz_spec);
 }
@@ -0,0 +1,287 @@
+use crate::cache;
+use crate::config::GitConfig;
+use crate::fsutil;
+use crate::git::{self, GitBackend};
+use crate::process;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+/// Upper bound on a `jj` or `hg` subprocess. Both start slower than git (hg
+/// is Python), so they get a little more room than `GIT_TIMEOUT`.
+const VCS_TIMEOUT: Duration = Duration::from_millis(800);
+
+/// A version-control system the working directory is checked out from.
+pub trait Vcs {
+    /// Branch or bookmark naming the working revision; None when nothing does.
+    fn branch(&self) -> Option<String>;
+    /// Short id of the working revision: git commit, jj change, hg node.
+    fn change_id(&self) -> Option<String>;
+    /// Web URL of the default remote.
+    fn remote_url(&self) -> Option<String>;
+    /// Whether the working copy has uncommitted changes; None when unknown or
+    /// left to a dedicated field.
+    fn is_dirty(&self) -> Option<bool>;
+    /// Branch (or commit) page and compare view the `branch` and `diff`
+    /// fields link to.
+    fn links(&self) -> (Option<String>, Option<String>) {
+        (None, None)
+    }
+}
+
+/// What the `dir`, `branch` and `diff` fields show for the checkout, with
+/// the links each is wrapped in.
+pub struct VcsInfo {
+    /// Branch label, e.g. `main`, `feature-x|REBASE 3/7`, `@kxqpmrlu*`.
+    pub branch: String,
+    pub remote_url: Option<String>,
+    pub branch_url: Option<String>,
+    pub compare_url: Option<String>,
+}
+
+/// The dirty check is only made with `dirty`, when the `branch` field that
+/// shows it is rendered.
+pub fn get_vcs_info(cwd: &str, transcript_path: &str, settings: &GitConfig, dirty: bool) -> VcsInfo {
+    let Some(vcs) = detect(cwd, transcript_path, settings) else {
+        return VcsInfo { branch: "no-git".to_string(), remote_url: None, branch_url: None, compare_url: None };
+    };
+    let mut branch = vcs.branch()
+        .or_else(|| vcs.change_id().map(|id| format!("@{}", id)))
+        .unwrap_or_else(|| "no-git".to_string());
+    if dirty && vcs.is_dirty() == Some(true) {
+        branch.push('*');
+    }
+    let (branch_url, compare_url) = vcs.links();
+    VcsInfo { branch, remote_url: vcs.remote_url(), branch_url, compare_url }
+}
+
+/// The innermost checkout containing `cwd`. At each level `.jj` wins over
+/// `.git`, since a colocated jj repo has both and jj is the one in use.
+/// Dirty checks are cached in the session cache of `transcript_path`.
+pub fn detect(cwd: &str, transcript_path: &str, settings: &GitConfig) -> Option<Box<dyn Vcs>> {
+    let mut dir = Some(Path::new(cwd));
+    while let Some(d) = dir {
+        if d.join(".jj").is_dir() {
+            return Some(Box::new(Jj::open(d, transcript_path, settings)));
+        }
+        if d.join(".git").exists() {
+            break;
+        }
+        if d.join(".hg").is_dir() {
+            return Some(Box::new(Hg::open(d, transcript_path, settings)));
+        }
+        dir = d.parent();
+    }
+    // Git also covers bare repositories and `.git` files, which it resolves itself.
+    GitBackend::open(cwd, transcript_path, settings).map(|g| Box::new(g) as Box<dyn Vcs>)
+}
+
+/// A Jujutsu workspace. Everything comes from one bounded `jj log` call;
+/// remotes are read from the backing git repository.
+struct Jj {
+    status: Option<JjStatus>,
+    remote_url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct JjStatus {
+    change_id: String,
+    bookmarks: Vec<String>,
+    /// Bookmarks on `@-`: a fresh change on top of `main` shows as `main`.
+    parent_bookmarks: Vec<String>,
+    /// As of jj's last snapshot: edits since then aren't seen until some
+    /// other jj command snapshots them.
+    dirty: bool,
+}
+
+const JJ_TEMPLATE: &str = concat!(
+    r#"change_id.shortest(8) ++ "\t" ++ "#,
+    r#"local_bookmarks.map(|b| b.name()).join(",") ++ "\t" ++ "#,
+    r#"parents.map(|c| c.local_bookmarks().map(|b| b.name()).join(",")).join(",") ++ "\t" ++ "#,
+    r#"if(empty, "0", "1") ++ "\n""#,
+);
+
+impl Jj {
+    fn open(root: &Path, transcript_path: &str, settings: &GitConfig) -> Jj {
+        let remote_url = jj_git_dir(root).and_then(|g| git::remote_url_for_git_dir(&g, settings));
+        Jj { status: jj_status(root, transcript_path), remote_url }
+    }
+}
+
+/// `jj log` for `@`, cached in the session cache. Without a snapshot its
+/// output only changes with a new operation, which moves the op heads, and
+/// anything else is caught once `git::STATUS_TTL` passes. A failed or
+/// timed-out call is cached too, so it isn't paid on every render.
+fn jj_status(root: &Path, transcript_path: &str) -> Option<JjStatus> {
+    let root_str = root.to_string_lossy().into_owned();
+    let op_heads_mtime_ms = jj_repo_dir(root)
+        .and_then(|r| fsutil::mtime_ms(&r.join("op_heads").join("heads")))
+        .unwrap_or(0);
+    let now_ms = fsutil::now_ms();
+
+    let cached = cache::load_raw(transcript_path);
+    if let Some(j) = cached.as_ref().and_then(|c| c.vcs.jj_status.as_ref())
+        && j.root == root_str
+        && j.op_heads_mtime_ms == op_heads_mtime_ms
+        && now_ms.saturating_sub(j.checked_ms) < git::STATUS_TTL.as_millis() {
+            return j.status.clone();
+        }
+
+    let mut cmd = Command::new("jj");
+    // --ignore-working-copy: don't snapshot (and so write to) the repo
+    // behind the agent's back on every render.
+    cmd.arg("-R").arg(root)
+        .args(["--ignore-working-copy", "--no-pager", "--color", "never"])
+        .args(["log", "--no-graph", "-r", "@", "-T", JJ_TEMPLATE]);
+    let status = process::output_with_timeout(&mut cmd, VCS_TIMEOUT).and_then(|out| parse_jj_log(&out));
+
+    cache::update(transcript_path, |c| {
+        c.vcs.jj_status = Some(cache::JjStatusCache { root: root_str, op_heads_mtime_ms, checked_ms: now_ms, status: status.clone() });
+    });
+    status
+}
+
+fn parse_jj_log(out: &str) -> Option<JjStatus> {
+    let mut parts = out.lines().next()?.split('\t');
+    let change_id = parts.next().filter(|s| !s.is_empty())?.to_string();
+    let list = |s: Option<&str>| -> Vec<String> {
+        s.unwrap_or("").split(',').filter(|b| !b.is_empty()).map(String::from).collect()
+    };
+    let bookmarks = list(parts.next());
+    let parent_bookmarks = list(parts.next());
+    let dirty = parts.next() == Some("1");
+    Some(JjStatus { change_id, bookmarks, parent_bookmarks, dirty })
+}
+
+/// A jj workspace's repo directory: `.jj/repo`, or where that file points
+/// in secondary workspaces.
+fn jj_repo_dir(root: &Path) -> Option<PathBuf> {
+    let repo = root.join(".jj").join("repo");
+    if repo.is_file() {
+        let target = fs::read_to_string(&repo).ok()?;
+        return Some(root.join(".jj").join(target.trim()));
+    }
+    Some(repo)
+}
+
+/// The git repository backing a jj workspace: `store/git_target` in its repo
+/// directory holds a path relative to the store.
+fn jj_git_dir(root: &Path) -> Option<PathBuf> {
+    let store = jj_repo_dir(root)?.join("store");
+    let target = fs::read_to_string(store.join("git_target")).ok()?;
+    Some(store.join(target.trim()))
+}
+
+impl Vcs for Jj {
+    fn branch(&self) -> Option<String> {
+        let s = self.status.as_ref()?;
+        let names = if s.bookmarks.is_empty() { &s.parent_bookmarks } else { &s.bookmarks };
+        names.first().cloned()
+    }
+
+    fn change_id(&self) -> Option<String> {
+        self.status.as_ref().map(|s| s.change_id.clone())
+    }
+
+    fn remote_url(&self) -> Option<String> {
+        self.remote_url.clone()
+    }
+
+    fn is_dirty(&self) -> Option<bool> {
+        self.status.as_ref().map(|s| s.dirty)
+    }
+}
+
+/// A Mercurial repository. Branch, bookmark and parent are read from `.hg`
+/// directly; only the dirty check runs `hg`.
+struct Hg {
+    root: PathBuf,
+    transcript_path: String,
+    remote_url: Option<String>,
+}
+
+impl Hg {
+    fn open(root: &Path, transcript_path: &str, settings: &GitConfig) -> Hg {
+        let remote_url = hg_default_path(&root.join(".hg").join("hgrc")).and_then(|url| git::web_url(&url, settings));
+        Hg { root: root.to_path_buf(), transcript_path: transcript_path.to_string(), remote_url }
+    }
+
+    fn hg_dir(&self) -> PathBuf {
+        self.root.join(".hg")
+    }
+}
+
+/// `[paths] default` from an hgrc.
+fn hg_default_path(hgrc: &Path) -> Option<String> {
+    let content = fs::read_to_string(hgrc).ok()?;
+    let mut in_paths = false;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_paths = line == "[paths]";
+        } else if in_paths
+            && let Some((key, value)) = line.split_once('=')
+            && key.trim() == "default" {
+                return Some(value.trim().to_string());
+            }
+    }
+    None
+}
+
+impl Vcs for Hg {
+    /// The active bookmark, else the named branch (`default` when unset).
+    fn branch(&self) -> Option<String> {
+        let read = |name: &str| {
+            fs::read_to_string(self.hg_dir().join(name)).ok()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+        };
+        read("bookmarks.current").or_else(|| read("branch")).or_else(|| Some("default".to_string()))
+    }
+
+    /// First parent from the dirstate; v2 dirstates put it after a marker line.
+    fn change_id(&self) -> Option<String> {
+        let data = fs::read(self.hg_dir().join("dirstate")).ok()?;
+        let data = data.strip_prefix(b"dirstate-v2\n".as_slice()).unwrap_or(&data);
+        let node = data.get(..20)?;
+        if node.iter().all(|&b| b == 0) {
+            return None;
+        }
+        Some(node[..6].iter().map(|b| format!("{:02x}", b)).collect())
+    }
+
+    fn remote_url(&self) -> Option<String> {
+        self.remote_url.clone()
+    }
+
+    /// Cached like `git::get_git_status`: the dirstate changes on every add,
+    /// commit and update, the transcript on every agent edit, and anything
+    /// else is caught once `git::STATUS_TTL` passes.
+    fn is_dirty(&self) -> Option<bool> {
+        let root = self.root.to_string_lossy().into_owned();
+        let dirstate_mtime_ms = fsutil::mtime_ms(&self.hg_dir().join("dirstate")).unwrap_or(0);
+        let transcript_size = cache::get_file_size(&self.transcript_path);
+        let now_ms = fsutil::now_ms();
+
+        let cached = cache::load_raw(&self.transcript_path);
+        if let Some(h) = cached.as_ref().and_then(|c| c.vcs.hg_status.as_ref())
+            && h.root == root
+            && h.dirstate_mtime_ms == dirstate_mtime_ms
+            && h.transcript_size == transcript_size
+            && now_ms.saturating_sub(h.checked_ms) < git::STATUS_TTL.as_millis() {
+                return Some(h.dirty);
+            }
+
+        let mut cmd = Command::new("hg");
+        // HGPLAIN: ignore user aliases and output tweaks.
+        cmd.env("HGPLAIN", "1").arg("--cwd").arg(&self.root).args(["status", "-mard"]);
+        let dirty = !process::output_with_timeout(&mut cmd, VCS_TIMEOUT)?.trim().is_empty();
+
+        cache::update(&self.transcript_path, |c| {
+            c.vcs.hg_status = Some(cache::HgStatusCache { root, dirstate_mtime_ms, transcript_size, checked_ms: now_ms, dirty });
+        });
+        Some(dirty)
+    }
+}
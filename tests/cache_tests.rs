@@ -0,0 +1,312 @@
+use serde_json::json;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+
+static TEST_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+fn unique_id() -> u64 {
+    TEST_COUNTER.fetch_add(1, Ordering::SeqCst)
+}
+
+/// A sandbox HOME and XDG_CACHE_HOME, and a session transcript with one edit.
+fn sandbox() -> (PathBuf, PathBuf, PathBuf) {
+    let home = std::env::temp_dir().join(format!("test_cache_{}_{}", std::process::id(), unique_id()));
+    let xdg = home.join("xdg");
+    fs::create_dir_all(home.join(".claude")).unwrap();
+    let config = json!({ "lines": [{ "fields": ["diff"], "separator": "|" }] });
+    fs::write(home.join(".claude/statusline.json"), config.to_string()).unwrap();
+    let file = home.join("a.txt");
+    fs::write(&file, "new\n").unwrap();
+    let transcript = home.join(format!("session-{}.jsonl", unique_id()));
+    let entry = json!({ "toolUseResult": { "filePath": file, "oldString": "old\n", "newString": "new\n" } });
+    fs::write(&transcript, format!("{}\n", entry)).unwrap();
+    (home, xdg, transcript)
+}
+
+fn render(home: &Path, xdg: &Path, transcript: &Path) -> String {
+    let input = json!({ "cwd": home, "transcript_path": transcript, "model": { "display_name": "test" } });
+    let output = Command::new(env!("CARGO_BIN_EXE_statusline"))
+        .env("HOME", home)
+        .env("XDG_CACHE_HOME", xdg)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            child.stdin.as_mut().unwrap().write_all(input.to_string().as_bytes())?;
+            child.wait_with_output()
+        })
+        .expect("Failed to run statusline");
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+fn stem(transcript: &Path) -> String {
+    transcript.file_stem().unwrap().to_string_lossy().into_owned()
+}
+
+/// A session cache for `transcript` last written `age` ago.
+fn write_cache(dir: &Path, name: &str, transcript: &Path, age: Duration) -> PathBuf {
+    fs::create_dir_all(dir).unwrap();
+    let path = dir.join(format!("session-{name}.json"));
+    fs::write(&path, json!({ "version": 2, "transcript": transcript }).to_string()).unwrap();
+    let f = fs::File::options().write(true).open(&path).unwrap();
+    f.set_modified(SystemTime::now() - age).unwrap();
+    path
+}
+
+#[test]
+fn test_cache_lives_in_private_xdg_dir() {
+    let (home, xdg, transcript) = sandbox();
+    let out = render(&home, &xdg, &transcript);
+    let dir = xdg.join("statusline");
+    let mode = fs::metadata(&dir).map(|m| m.permissions().mode() & 0o777);
+    let session = dir.join(format!("session-{}.json", stem(&transcript)));
+    let diff_state = dir.join(format!("diff-{}.json", stem(&transcript)));
+    let (has_session, has_diff_state) = (session.exists(), diff_state.exists());
+    let _ = fs::remove_dir_all(&home);
+    assert!(out.contains("+1"), "{out}");
+    assert_eq!(mode.ok(), Some(0o700));
+    assert!(has_session && has_diff_state);
+}
+
+#[test]
+fn test_sweep_drops_orphaned_and_expired_caches() {
+    let (home, xdg, transcript) = sandbox();
+    let dir = xdg.join("statusline");
+    let orphaned = write_cache(&dir, "gone", &home.join("gone.jsonl"), Duration::ZERO);
+    let orphaned_state = dir.join("diff-gone.json");
+    fs::write(&orphaned_state, "{}").unwrap();
+    let expired = write_cache(&dir, "old", &transcript, Duration::from_secs(60 * 86400));
+    let live = write_cache(&dir, "live", &transcript, Duration::from_secs(86400));
+    // Locks left by `cache clear`: a stale one goes, a fresh one stays.
+    let lock = |name: &str, age: Duration| {
+        let path = dir.join(format!("session-{name}.lock"));
+        fs::File::create(&path).unwrap().set_modified(SystemTime::now() - age).unwrap();
+        path
+    };
+    let stale_lock = lock("cleared", Duration::from_secs(2 * 86400));
+    let fresh_lock = lock("fresh", Duration::ZERO);
+
+    render(&home, &xdg, &transcript);
+    let remaining = (orphaned.exists(), orphaned_state.exists(), expired.exists(), live.exists());
+    let locks = (stale_lock.exists(), fresh_lock.exists());
+    // The sweep already ran today; a new orphan waits for tomorrow's.
+    let later = write_cache(&dir, "later", &home.join("gone.jsonl"), Duration::ZERO);
+    render(&home, &xdg, &transcript);
+    let later_kept = later.exists();
+    let _ = fs::remove_dir_all(&home);
+    assert_eq!(remaining, (false, false, false, true));
+    assert_eq!(locks, (false, true));
+    assert!(later_kept);
+}
+
+#[test]
+fn test_sweep_removes_old_tmp_caches() {
+    let (home, xdg, transcript) = sandbox();
+    let tag = format!("{}_{}", std::process::id(), unique_id());
+    let tmp = Path::new("/tmp");
+    let old_cache = tmp.join(format!("statusline_cache_{tag}.json"));
+    let old_state = tmp.join(format!("statusline_diff_{tag}.json"));
+    let unrelated = tmp.join(format!("statusline_other_{tag}.json"));
+    for f in [&old_cache, &old_state, &unrelated] {
+        fs::write(f, "{}").unwrap();
+    }
+
+    render(&home, &xdg, &transcript);
+    let removed = (old_cache.exists(), old_state.exists(), unrelated.exists());
+    // Recorded, so later sweeps don't look again.
+    let marked = xdg.join("statusline/.tmp-caches-removed").exists();
+    let _ = fs::remove_file(&unrelated);
+    let _ = fs::remove_dir_all(&home);
+    assert_eq!(removed, (false, false, true));
+    assert!(marked);
+}
+
+#[test]
+fn test_sweep_max_age_is_configurable() {
+    let (home, xdg, transcript) = sandbox();
+    let config = json!({ "lines": [{ "fields": ["diff"], "separator": "|" }], "cache": { "max_age_days": 0 } });
+    fs::write(home.join(".claude/statusline.json"), config.to_string()).unwrap();
+    let old = write_cache(&xdg.join("statusline"), "old", &transcript, Duration::from_secs(400 * 86400));
+
+    render(&home, &xdg, &transcript);
+    let kept = old.exists();
+    let _ = fs::remove_dir_all(&home);
+    assert!(kept);
+}
+
+/// A cached diff total that's current for `transcript`, so a render shows it
+/// instead of recomputing.
+fn current_diff(transcript: &Path, added: usize) -> serde_json::Value {
+    let size = fs::metadata(transcript).unwrap().len();
+    json!({ "byte_offset": size, "added": added, "removed": 0, "files": [] })
+}
+
+fn session_cache(xdg: &Path, transcript: &Path) -> PathBuf {
+    xdg.join("statusline").join(format!("session-{}.json", stem(transcript)))
+}
+
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::new();
+    let mut in_escape = false;
+    for c in s.chars() {
+        if c == '\x1b' {
+            in_escape = true;
+        } else if in_escape {
+            in_escape = c != 'm';
+        } else {
+            out.push(c);
+        }
+    }
+    out.trim().to_string()
+}
+
+#[test]
+fn test_unversioned_cache_is_migrated() {
+    let (home, xdg, transcript) = sandbox();
+    let path = session_cache(&xdg, &transcript);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    // The layout before sections: every collector's fields at the top level.
+    let mut v1 = current_diff(&transcript, 99);
+    v1["claude_pid"] = json!(4242);
+    fs::write(&path, v1.to_string()).unwrap();
+
+    let out = strip_ansi(&render(&home, &xdg, &transcript));
+    let _ = fs::remove_dir_all(&home);
+    assert_eq!(out, "+99 -0");
+}
+
+#[test]
+fn test_cache_from_unknown_version_is_discarded() {
+    let (home, xdg, transcript) = sandbox();
+    let path = session_cache(&xdg, &transcript);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(&path, json!({ "version": 99, "diff": current_diff(&transcript, 99) }).to_string()).unwrap();
+
+    let out = strip_ansi(&render(&home, &xdg, &transcript));
+    let rewritten: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    let _ = fs::remove_dir_all(&home);
+    assert_eq!(out, "+1 -1");
+    assert_eq!(rewritten["version"], json!(2));
+}
+
+#[test]
+fn test_unreadable_section_does_not_discard_the_others() {
+    let (home, xdg, transcript) = sandbox();
+    let path = session_cache(&xdg, &transcript);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let cache = json!({ "version": 2, "diff": current_diff(&transcript, 99), "git": { "status": "not a status" } });
+    fs::write(&path, cache.to_string()).unwrap();
+
+    let out = strip_ansi(&render(&home, &xdg, &transcript));
+    let _ = fs::remove_dir_all(&home);
+    assert_eq!(out, "+99 -0");
+}
+
+#[test]
+fn test_diff_update_keeps_other_sections() {
+    let (home, xdg, transcript) = sandbox();
+    let path = session_cache(&xdg, &transcript);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let cache = json!({
+        "version": 2,
+        "process": { "claude_pid": 4242 },
+        "git": { "session_base": { "work_tree": "/repo", "sha": "abc" } },
+    });
+    fs::write(&path, cache.to_string()).unwrap();
+
+    render(&home, &xdg, &transcript);
+    let after: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    let _ = fs::remove_dir_all(&home);
+    assert_eq!(after["diff"]["added"], json!(1));
+    assert_eq!(after["process"]["claude_pid"], json!(4242));
+    assert_eq!(after["git"]["session_base"]["sha"], json!("abc"));
+}
+
+fn run_cache(home: &Path, xdg: &Path, args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_statusline"))
+        .arg("cache")
+        .args(args)
+        .env("HOME", home)
+        .env("XDG_CACHE_HOME", xdg)
+        .output()
+        .expect("Failed to run statusline")
+}
+
+/// Turns on usage tracking and gives it one transcript to fold, so a render
+/// leaves a usage cache behind.
+fn enable_usage(home: &Path) {
+    let config = json!({ "lines": [{ "fields": ["diff"], "separator": "|" }], "track_usage": { "enabled": true } });
+    fs::write(home.join(".claude/statusline.json"), config.to_string()).unwrap();
+    let project = home.join(".claude/projects/p");
+    fs::create_dir_all(&project).unwrap();
+    let line = json!({
+        "type": "assistant",
+        "timestamp": "2099-01-01T10:00:00Z",
+        "sessionId": "s",
+        "message": { "id": "m", "model": "claude-sonnet-4-5", "usage": { "input_tokens": 1, "output_tokens": 1 } },
+    });
+    fs::write(project.join("s.jsonl"), format!("{}\n", line)).unwrap();
+}
+
+#[test]
+fn test_cache_status_lists_sessions_and_usage() {
+    let (home, xdg, transcript) = sandbox();
+    enable_usage(&home);
+    let dir = xdg.join("statusline");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(session_cache(&xdg, &transcript), json!({ "version": 2, "process": { "claude_pid": 4242 } }).to_string()).unwrap();
+    fs::write(dir.join("project--src-app.json"), "{}").unwrap();
+    render(&home, &xdg, &transcript);
+
+    let out = run_cache(&home, &xdg, &["status"]);
+    let size = fs::metadata(&transcript).unwrap().len();
+    let _ = fs::remove_dir_all(&home);
+    let stdout = String::from_utf8_lossy(&out.stdout).to_string();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines[0], format!("Session caches in {}: 1", dir.display()));
+    assert_eq!(lines[1], format!("  {}  offset {}  1 file  pid 4242  {}", stem(&transcript), size, transcript.display()));
+    assert_eq!(lines[2], "Project caches: 1, 2 B");
+    assert!(lines[3].starts_with(&format!("Usage cache {}: ", home.join(".claude/usage/usage-cache.json").display())), "{stdout}");
+    assert!(lines[3].ends_with(", 1 transcript"), "{stdout}");
+}
+
+#[test]
+fn test_cache_clear() {
+    let (home, xdg, transcript) = sandbox();
+    enable_usage(&home);
+    let other = home.join("other.jsonl");
+    fs::write(&other, "").unwrap();
+    render(&home, &xdg, &transcript);
+    render(&home, &xdg, &other);
+    let usage_cache = home.join(".claude/usage/usage-cache.json");
+    let dir = xdg.join("statusline");
+    let project = dir.join("project--src-app.json");
+    fs::write(&project, "{}").unwrap();
+
+    let one = run_cache(&home, &xdg, &["clear", "--session", &stem(&transcript)]);
+    let after_one = (session_cache(&xdg, &transcript).exists(), dir.join(format!("diff-{}.json", stem(&transcript))).exists(), session_cache(&xdg, &other).exists());
+    // The lock stays, so a render waiting on it and the next one share it.
+    let lock_kept = dir.join(format!("session-{}.lock", stem(&transcript))).exists();
+    let usage = run_cache(&home, &xdg, &["clear", "--usage"]);
+    let usage_len = fs::metadata(&usage_cache).map(|m| m.len()).ok();
+    let kept = (session_cache(&xdg, &other).exists(), project.exists());
+    let rest = run_cache(&home, &xdg, &["clear"]);
+    let other_kept = session_cache(&xdg, &other).exists() || project.exists();
+    let bad = run_cache(&home, &xdg, &["clear", "--session"]);
+    let _ = fs::remove_dir_all(&home);
+
+    assert_eq!(String::from_utf8_lossy(&one.stdout), "Removed 1 session cache\n");
+    assert_eq!(after_one, (false, false, true));
+    assert!(lock_kept);
+    assert_eq!(String::from_utf8_lossy(&usage.stdout), "Cleared usage cache\n");
+    assert_eq!(usage_len, Some(0));
+    assert_eq!(kept, (true, true));
+    assert_eq!(String::from_utf8_lossy(&rest.stdout), "Removed 1 session cache\nRemoved 1 project cache\n");
+    assert!(!other_kept);
+    assert_eq!(bad.status.code(), Some(2));
+}
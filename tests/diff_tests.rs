@@ -14,6 +14,8 @@ fn create_test_transcript(entries: &[&str]) -> String {
     let tmp_dir = std::env::temp_dir().join("statusline_test");
     let _ = fs::create_dir_all(&tmp_dir);
     let path = tmp_dir.join(format!("test_{}_{}.jsonl", std::process::id(), unique_id()));
+    // A cache left by an earlier run with the same pid would be stale.
+    let _ = fs::remove_dir_all(cache_home(&path.to_string_lossy()));
     let mut file = File::create(&path).unwrap();
     for entry in entries {
         writeln!(file, "{}", entry).unwrap();
@@ -21,6 +23,13 @@ fn create_test_transcript(entries: &[&str]) -> String {
     path.to_string_lossy().to_string()
 }
 
+/// Where runs on `transcript_path` keep their caches: beside the transcript,
+/// so a test's runs share one cache without touching another test's, or the
+/// real `~/.cache`.
+fn cache_home(transcript_path: &str) -> PathBuf {
+    PathBuf::from(format!("{}.cache", transcript_path))
+}
+
 fn write_entry(file_path: &str, original: &str, content: &str) -> String {
     // Use serde_json to properly escape strings
     let obj = serde_json::json!({
@@ -52,6 +61,7 @@ fn run_statusline(transcript_path: &str, test_file: &str) -> (usize, usize) {
     );
 
     let output = Command::new(env!("CARGO_BIN_EXE_statusline"))
+        .env("XDG_CACHE_HOME", cache_home(transcript_path))
         .stdin(std::process::Stdio::piped())
         .stdout(std::process::Stdio::piped())
         .spawn()
@@ -138,6 +148,7 @@ fn run_diff(transcript_path: &str) -> (usize, usize) {
         transcript_path
     );
     let output = Command::new(env!("CARGO_BIN_EXE_statusline"))
+        .env("XDG_CACHE_HOME", cache_home(transcript_path))
         .stdin(std::process::Stdio::piped())
         .stdout(std::process::Stdio::piped())
         .spawn()
@@ -855,3 +866,284 @@ fn test_write_after_edit_clears_chains() {
     assert_eq!(added, 3, "Should only count Write diff, not edit chain");
     assert_eq!(removed, 2, "Should only count Write diff, not edit chain");
 }
+
+/// Runs `statusline --diff-files` with `cwd` as the session's working directory.
+fn run_diff_files(transcript_path: &str, cwd: &Path) -> String {
+    run_report("--diff-files", transcript_path, cwd)
+}
+
+fn run_report(flag: &str, transcript_path: &str, cwd: &Path) -> String {
+    let input = serde_json::json!({
+        "cwd": cwd,
+        "transcript_path": transcript_path,
+        "model": { "display_name": "test" },
+    });
+    let output = Command::new(env!("CARGO_BIN_EXE_statusline"))
+        .env("XDG_CACHE_HOME", cache_home(transcript_path))
+        .arg(flag)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            child.stdin.as_mut().unwrap().write_all(input.to_string().as_bytes())?;
+            child.wait_with_output()
+        })
+        .expect("Failed to run statusline");
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+#[test]
+fn test_diff_files_breakdown() {
+    let dir = std::env::temp_dir().join(format!("test_diff_files_{}_{}", std::process::id(), unique_id()));
+    fs::create_dir_all(&dir).unwrap();
+    let small = dir.join("small.txt");
+    let big = dir.join("big.txt");
+    let sub = dir.join("sub.txt");
+    fs::write(&small, "a\nB\n").unwrap();
+    fs::write(&big, "1\n2\n3\n").unwrap();
+    fs::write(&sub, "x\n").unwrap();
+
+    let transcript = create_test_transcript(&[
+        &edit_entry(small.to_str().unwrap(), "b\n", "B\n"),
+        &write_entry(big.to_str().unwrap(), "", "1\n2\n3\n"),
+    ]);
+    write_subagent_transcript(&transcript, "agent-1", &[edit_entry(sub.to_str().unwrap(), "y\n", "x\n")]);
+
+    let out = run_diff_files(&transcript, &dir);
+    let _ = fs::remove_dir_all(&dir);
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines, vec![
+        "+3 -0  big.txt  (new)",
+        "+1 -1  small.txt",
+        "+1 -1  sub.txt  (subagent)",
+    ]);
+}
+
+#[test]
+fn test_diff_files_outside_cwd_shows_absolute_path() {
+    let file = std::env::temp_dir().join(format!("test_diff_files_abs_{}.txt", unique_id()));
+    fs::write(&file, "new\n").unwrap();
+    let transcript = create_test_transcript(&[&edit_entry(file.to_str().unwrap(), "old\n", "new\n")]);
+
+    let out = run_diff_files(&transcript, Path::new("/nonexistent-cwd"));
+    let _ = fs::remove_file(&file);
+    assert_eq!(out.trim(), format!("+1 -1  {}", file.display()));
+}
+
+/// Renders `diff` and `diff-excluded` for a session in `cwd` with the given
+/// `diff` config section, returning the ANSI-stripped output.
+fn run_filtered(transcript_path: &str, cwd: &Path, diff_cfg: serde_json::Value) -> String {
+    run_fields(transcript_path, cwd, &["diff", "diff-excluded"], diff_cfg)
+}
+
+fn run_fields(transcript_path: &str, cwd: &Path, fields: &[&str], diff_cfg: serde_json::Value) -> String {
+    let home = std::env::temp_dir().join(format!("test_diff_home_{}_{}", std::process::id(), unique_id()));
+    fs::create_dir_all(home.join(".claude")).unwrap();
+    let config = serde_json::json!({
+        "lines": [{ "fields": fields, "separator": "|" }],
+        "diff": diff_cfg,
+    });
+    fs::write(home.join(".claude/statusline.json"), config.to_string()).unwrap();
+    let input = serde_json::json!({
+        "cwd": cwd,
+        "transcript_path": transcript_path,
+        "model": { "display_name": "test" },
+    });
+    let output = Command::new(env!("CARGO_BIN_EXE_statusline"))
+        .env("XDG_CACHE_HOME", cache_home(transcript_path))
+        .env("HOME", &home)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            child.stdin.as_mut().unwrap().write_all(input.to_string().as_bytes())?;
+            child.wait_with_output()
+        })
+        .expect("Failed to run statusline");
+    let _ = fs::remove_dir_all(&home);
+    let mut out = String::new();
+    let mut in_escape = false;
+    for c in String::from_utf8_lossy(&output.stdout).chars() {
+        if c == '\x1b' {
+            in_escape = true;
+        } else if in_escape {
+            in_escape = c != 'm';
+        } else {
+            out.push(c);
+        }
+    }
+    out.trim().to_string()
+}
+
+/// A fake repository with `src/main.rs` (one line changed) and `Cargo.lock`
+/// (three lines written), plus a file outside it (one line written).
+fn setup_filter_session() -> (PathBuf, String, PathBuf) {
+    let repo = std::env::temp_dir().join(format!("test_diff_filter_{}_{}", std::process::id(), unique_id()));
+    fs::create_dir_all(repo.join(".git")).unwrap();
+    fs::write(repo.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+    fs::create_dir_all(repo.join("src")).unwrap();
+    let main_rs = repo.join("src/main.rs");
+    let lock = repo.join("Cargo.lock");
+    let outside = std::env::temp_dir().join(format!("test_diff_filter_outside_{}.txt", unique_id()));
+    fs::write(&main_rs, "new\n").unwrap();
+    fs::write(&lock, "a\nb\nc\n").unwrap();
+    fs::write(&outside, "x\n").unwrap();
+    let transcript = create_test_transcript(&[
+        &edit_entry(main_rs.to_str().unwrap(), "old\n", "new\n"),
+        &write_entry(lock.to_str().unwrap(), "", "a\nb\nc\n"),
+        &write_entry(outside.to_str().unwrap(), "", "x\n"),
+    ]);
+    (repo, transcript, outside)
+}
+
+#[test]
+fn test_diff_exclude_glob() {
+    let (repo, transcript, outside) = setup_filter_session();
+    let out = run_filtered(&transcript, &repo, serde_json::json!({ "exclude": ["*.lock"] }));
+    let _ = fs::remove_dir_all(&repo);
+    let _ = fs::remove_file(&outside);
+    assert_eq!(out, "+2 -1 | excluded:3");
+}
+
+#[test]
+fn test_diff_include_glob_relative_to_repo() {
+    let (repo, transcript, outside) = setup_filter_session();
+    let out = run_filtered(&transcript, &repo.join("src"), serde_json::json!({ "include": ["src/**"] }));
+    let _ = fs::remove_dir_all(&repo);
+    let _ = fs::remove_file(&outside);
+    assert_eq!(out, "+1 -1 | excluded:4");
+}
+
+#[test]
+fn test_diff_repo_only() {
+    let (repo, transcript, outside) = setup_filter_session();
+    let out = run_filtered(&transcript, &repo, serde_json::json!({ "repo_only": true }));
+    let _ = fs::remove_dir_all(&repo);
+    let _ = fs::remove_file(&outside);
+    assert_eq!(out, "+4 -1 | excluded:1");
+}
+
+#[test]
+fn test_diff_excluded_hidden_without_filter() {
+    let (repo, transcript, outside) = setup_filter_session();
+    let out = run_filtered(&transcript, &repo, serde_json::json!({}));
+    let _ = fs::remove_dir_all(&repo);
+    let _ = fs::remove_file(&outside);
+    assert_eq!(out, "+5 -1");
+}
+
+#[test]
+fn test_diff_filter_change_invalidates_cache() {
+    let (repo, transcript, outside) = setup_filter_session();
+    let first = run_filtered(&transcript, &repo, serde_json::json!({}));
+    let second = run_filtered(&transcript, &repo, serde_json::json!({ "exclude": ["*.lock"] }));
+    let _ = fs::remove_dir_all(&repo);
+    let _ = fs::remove_file(&outside);
+    assert_eq!((first.as_str(), second.as_str()), ("+5 -1", "+2 -1 | excluded:3"));
+}
+
+#[test]
+fn test_diff_drift_after_outside_edit() {
+    let (repo, transcript, outside) = setup_filter_session();
+    let fields = ["diff", "diff-drift"];
+    let before = run_fields(&transcript, &repo, &fields, serde_json::json!({}));
+    // The lockfile is regenerated after the session wrote it.
+    fs::write(repo.join("Cargo.lock"), "a\nb\nc\nd\n").unwrap();
+    let after = run_fields(&transcript, &repo, &fields, serde_json::json!({}));
+    let _ = fs::remove_dir_all(&repo);
+    let _ = fs::remove_file(&outside);
+    assert_eq!(before, "+5 -1");
+    assert_eq!(after, "+5 -1 | drift:1");
+}
+
+#[test]
+fn test_diff_on_disk_counts_drifted_file_content() {
+    let (repo, transcript, outside) = setup_filter_session();
+    fs::write(repo.join("Cargo.lock"), "a\nc\nd\ne\n").unwrap();
+    let out = run_fields(&transcript, &repo, &["diff", "diff-drift"], serde_json::json!({ "on_disk": true }));
+    let _ = fs::remove_dir_all(&repo);
+    let _ = fs::remove_file(&outside);
+    assert_eq!(out, "+6 -1 | drift:1");
+}
+
+#[test]
+fn test_diff_agents_field_splits_main_and_subagents() {
+    let dir = std::env::temp_dir().join(format!("test_diff_agents_{}_{}", std::process::id(), unique_id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (main_file, first, second) = (dir.join("main.txt"), dir.join("first.txt"), dir.join("second.txt"));
+    fs::write(&main_file, "1\n2\n3\n").unwrap();
+    fs::write(&first, "x\ny\n").unwrap();
+    fs::write(&second, "z\n").unwrap();
+    let transcript = create_test_transcript(&[&write_entry(main_file.to_str().unwrap(), "", "1\n2\n3\n")]);
+    write_subagent_transcript(&transcript, "agent-1", &[write_entry(first.to_str().unwrap(), "", "x\ny\n")]);
+    write_subagent_transcript(&transcript, "agent-2", &[write_entry(second.to_str().unwrap(), "", "z\n")]);
+
+    let out = run_fields(&transcript, &dir, &["diff", "diff-agents"], serde_json::json!({}));
+    let _ = fs::remove_dir_all(&dir);
+    assert_eq!(out, "+6 -0 | main +3 -0 · 2 agents +3 -0");
+}
+
+#[test]
+fn test_diff_agents_hidden_without_subagents() {
+    let file = std::env::temp_dir().join(format!("test_diff_agents_solo_{}.txt", unique_id()));
+    fs::write(&file, "new\n").unwrap();
+    let transcript = create_test_transcript(&[&edit_entry(file.to_str().unwrap(), "old\n", "new\n")]);
+    let out = run_fields(&transcript, Path::new("/tmp"), &["diff", "diff-agents"], serde_json::json!({}));
+    let _ = fs::remove_file(&file);
+    assert_eq!(out, "+1 -1");
+}
+
+#[test]
+fn test_diff_agents_report_splits_shared_file_by_own_edits() {
+    let dir = std::env::temp_dir().join(format!("test_diff_agents_report_{}_{}", std::process::id(), unique_id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (shared, own) = (dir.join("shared.txt"), dir.join("own.txt"));
+    fs::write(&shared, "a\nB\nc\n").unwrap();
+    fs::write(&own, "x\n").unwrap();
+    let write_ts = |path: &Path, content: &str, ts: &str| {
+        serde_json::json!({
+            "timestamp": ts,
+            "toolUseResult": { "filePath": path, "originalFile": "", "content": content },
+        }).to_string()
+    };
+    let transcript = create_test_transcript(&[&write_ts(&shared, "a\nb\nc\n", "2025-01-01T00:00:01Z")]);
+    write_subagent_transcript(&transcript, "agent-1", &[
+        edit_entry_ts(shared.to_str().unwrap(), "b\n", "B\n", "2025-01-01T00:00:02Z"),
+        write_ts(&own, "x\n", "2025-01-01T00:00:03Z"),
+    ]);
+
+    let out = run_report("--diff-agents", &transcript, &dir);
+    let _ = fs::remove_dir_all(&dir);
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines, vec![
+        "+3 -0  main     1 file",
+        "+2 -1  agent-1  2 files",
+    ]);
+}
+
+#[test]
+fn test_files_fields() {
+    let (repo, transcript, outside) = setup_filter_session();
+    let fields = ["files", "files-new", "files-deleted", "files-touched-outside-repo"];
+    let out = run_fields(&transcript, &repo, &fields, serde_json::json!({}));
+    let _ = fs::remove_dir_all(&repo);
+    let _ = fs::remove_file(&outside);
+    assert_eq!(out, "3 files | 2 new | 1 outside repo");
+}
+
+#[test]
+fn test_files_deleted_field() {
+    let dir = std::env::temp_dir().join(format!("test_files_deleted_{}_{}", std::process::id(), unique_id()));
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("old.txt");
+    let rm = serde_json::json!({
+        "type": "assistant",
+        "cwd": dir,
+        "message": { "content": [{ "type": "tool_use", "name": "Bash", "input": { "command": "rm old.txt" } }] },
+    });
+    let transcript = create_test_transcript(&[&edit_entry(file.to_str().unwrap(), "a\n", "b\n"), &rm.to_string()]);
+
+    let out = run_fields(&transcript, &dir, &["files", "files-new", "files-deleted"], serde_json::json!({}));
+    let _ = fs::remove_dir_all(&dir);
+    assert_eq!(out, "1 file | 1 deleted");
+}
@@ -0,0 +1,102 @@
+//! Session diff counting for MultiEdit and NotebookEdit results, from
+//! transcript fixtures in `tests/fixtures/`. `{{FILE}}` in a fixture stands
+//! for the edited file's path, which each test creates on disk.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde_json::json;
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+fn temp_path(name: &str) -> PathBuf {
+    let id = COUNTER.fetch_add(1, Ordering::SeqCst);
+    std::env::temp_dir().join(format!("edit_tool_{}_{}_{}", std::process::id(), id, name))
+}
+
+/// Writes `fixture` as a transcript with `{{FILE}}` pointing at `file`.
+fn load_fixture(fixture: &str, file: &Path) -> PathBuf {
+    let text = fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(fixture)).unwrap();
+    let transcript = temp_path("transcript.jsonl");
+    let _ = fs::remove_dir_all(transcript.with_extension("cache"));
+    fs::write(&transcript, text.replace("{{FILE}}", file.to_str().unwrap())).unwrap();
+    transcript
+}
+
+/// Renders only the `diff` field and parses `+a -r`.
+fn run_diff(transcript: &Path) -> (usize, usize) {
+    let home = temp_path("home");
+    fs::create_dir_all(home.join(".claude")).unwrap();
+    let config = json!({ "lines": [{ "fields": ["diff"], "separator": "|" }] });
+    fs::write(home.join(".claude/statusline.json"), config.to_string()).unwrap();
+    let input = json!({ "cwd": "/tmp", "transcript_path": transcript, "model": { "display_name": "test" } });
+
+    let output = Command::new(env!("CARGO_BIN_EXE_statusline"))
+        .env("HOME", &home)
+        // Beside the transcript, so it neither leaks into the real
+        // `~/.cache` nor outlives a rerun that reuses the name.
+        .env("XDG_CACHE_HOME", transcript.with_extension("cache"))
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            child.stdin.as_mut().unwrap().write_all(input.to_string().as_bytes())?;
+            child.wait_with_output()
+        })
+        .expect("Failed to run statusline");
+    let _ = fs::remove_dir_all(&home);
+
+    let mut text = String::new();
+    let mut in_escape = false;
+    for c in String::from_utf8_lossy(&output.stdout).chars() {
+        if c == '\x1b' {
+            in_escape = true;
+        } else if in_escape {
+            in_escape = c != 'm';
+        } else {
+            text.push(c);
+        }
+    }
+    let (a, r) = text.trim().split_once(' ').expect("diff output");
+    (a.trim_start_matches('+').parse().unwrap(), r.trim_start_matches('-').parse().unwrap())
+}
+
+const MAIN_RS_AFTER: &str = "fn main() {\n    let x = 10;\n    let y = 20;\n    let z = 3;\n    println!(\"{}\", x + y);\n}\n";
+
+#[test]
+fn test_multi_edit_with_structured_patch() {
+    let file = temp_path("main.rs");
+    fs::write(&file, MAIN_RS_AFTER).unwrap();
+    let transcript = load_fixture("multi_edit.jsonl", &file);
+    assert_eq!(run_diff(&transcript), (3, 2));
+}
+
+#[test]
+fn test_multi_edit_edits_array_only() {
+    // Older MultiEdit results: just the `edits` array, no snapshot or patch.
+    let file = temp_path("main.rs");
+    fs::write(&file, MAIN_RS_AFTER).unwrap();
+    let transcript = load_fixture("multi_edit_no_patch.jsonl", &file);
+    assert_eq!(run_diff(&transcript), (3, 2));
+}
+
+#[test]
+fn test_notebook_edit_counts_cell_source() {
+    // The outputs and execution count change too, but only the cell's source
+    // edit counts: one line replaced, one added.
+    let file = temp_path("analysis.ipynb");
+    fs::write(&file, "{}").unwrap();
+    let transcript = load_fixture("notebook_edit.jsonl", &file);
+    assert_eq!(run_diff(&transcript), (2, 1));
+}
+
+#[test]
+fn test_notebook_insert_without_snapshots() {
+    let file = temp_path("analysis.ipynb");
+    fs::write(&file, "{}").unwrap();
+    let transcript = load_fixture("notebook_insert_no_snapshot.jsonl", &file);
+    assert_eq!(run_diff(&transcript), (2, 0));
+}
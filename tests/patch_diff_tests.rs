@@ -29,6 +29,8 @@ fn create_test_transcript(entries: &[String]) -> String {
     let tmp_dir = std::env::temp_dir().join("statusline_test");
     let _ = fs::create_dir_all(&tmp_dir);
     let path = tmp_dir.join(format!("patch_test_{}_{}.jsonl", std::process::id(), unique_id()));
+    // A cache left by an earlier run with the same pid would be stale.
+    let _ = fs::remove_dir_all(cache_home(&path.to_string_lossy()));
     let mut file = File::create(&path).unwrap();
     for entry in entries {
         writeln!(file, "{}", entry).unwrap();
@@ -36,6 +38,13 @@ fn create_test_transcript(entries: &[String]) -> String {
     path.to_string_lossy().to_string()
 }
 
+/// Where runs on `transcript_path` keep their caches: beside the transcript,
+/// so a test's runs share one cache without touching another test's, or the
+/// real `~/.cache`.
+fn cache_home(transcript_path: &str) -> std::path::PathBuf {
+    std::path::PathBuf::from(format!("{}.cache", transcript_path))
+}
+
 /// Build a structuredPatch hunk. `lines` use unified-diff prefixes
 /// (' ' context, '+' add, '-' delete); old/new line counts are derived.
 fn hunk(old_start: u64, new_start: u64, lines: &[&str]) -> Value {
@@ -126,12 +135,21 @@ fn meta_line() -> String {
 }
 
 fn run_statusline(transcript_path: &str, test_file: &str) -> (usize, usize) {
+    let counts = render_diff(transcript_path);
+    let _ = fs::remove_file(test_file);
+    counts
+}
+
+/// Renders the status line for `transcript_path` and parses `+a -r`, leaving
+/// the edited files in place so a test can append and render again.
+fn render_diff(transcript_path: &str) -> (usize, usize) {
     let input = format!(
         r#"{{"cwd":"/tmp","transcript_path":"{}","model":{{"display_name":"test"}}}}"#,
         transcript_path
     );
 
     let output = Command::new(env!("CARGO_BIN_EXE_statusline"))
+        .env("XDG_CACHE_HOME", cache_home(transcript_path))
         .stdin(std::process::Stdio::piped())
         .stdout(std::process::Stdio::piped())
         .spawn()
@@ -175,7 +193,6 @@ fn run_statusline(transcript_path: &str, test_file: &str) -> (usize, usize) {
         }
     }
 
-    let _ = fs::remove_file(test_file);
     (added, removed)
 }
 
@@ -482,3 +499,307 @@ fn test_multi_hunk_patch() {
     let (added, removed) = run_statusline(&transcript, fp);
     assert_eq!((added, removed), (2, 1), "Multi-hunk patches must apply with correct offsets");
 }
+
+/// Runs `statusline --export-patch <transcript>` from `cwd`.
+fn export_patch(transcript_path: &str, cwd: &std::path::Path) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_statusline"))
+        .env("XDG_CACHE_HOME", cache_home(transcript_path))
+        .args(["--export-patch", transcript_path])
+        .current_dir(cwd)
+        .output()
+        .expect("Failed to run statusline");
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+#[test]
+fn test_export_patch_relative_to_repo_root() {
+    let repo = std::env::temp_dir().join(format!("patch_export_repo_{}_{}", std::process::id(), unique_id()));
+    fs::create_dir_all(repo.join(".git")).unwrap();
+    fs::write(repo.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+    fs::create_dir_all(repo.join("src")).unwrap();
+    let edited = repo.join("src/lib.rs");
+    let created = repo.join("NEW.md");
+    fs::write(&edited, "one\nTWO\nthree\n").unwrap();
+    fs::write(&created, "hello\n").unwrap();
+
+    let transcript = create_test_transcript(&[
+        edit_entry("e1", None, edited.to_str().unwrap(), "two", "TWO", Some("one\ntwo\nthree\n"),
+            &[hunk(1, 1, &[" one", "-two", "+TWO", " three"])]),
+        write_entry("w1", Some("e1"), created.to_str().unwrap(), None, "hello\n", &[]),
+    ]);
+
+    // Run from a subdirectory: paths are still relative to the repo root.
+    let patch = export_patch(&transcript, &repo.join("src"));
+    let _ = fs::remove_dir_all(&repo);
+    assert_eq!(patch, concat!(
+        "--- a/src/lib.rs\n",
+        "+++ b/src/lib.rs\n",
+        "@@ -1,3 +1,3 @@\n",
+        " one\n",
+        "-two\n",
+        "+TWO\n",
+        " three\n",
+        "--- /dev/null\n",
+        "+++ b/NEW.md\n",
+        "@@ -0,0 +1 @@\n",
+        "+hello\n",
+    ));
+}
+
+#[test]
+fn test_export_patch_relative_to_session_repo() {
+    let repo = std::env::temp_dir().join(format!("patch_export_session_{}_{}", std::process::id(), unique_id()));
+    fs::create_dir_all(repo.join(".git")).unwrap();
+    fs::write(repo.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+    fs::create_dir_all(repo.join("src")).unwrap();
+    let edited = repo.join("src/lib.rs");
+    fs::write(&edited, "b\n").unwrap();
+
+    let transcript = create_test_transcript(&[
+        json!({ "type": "system", "cwd": repo.join("src") }).to_string(),
+        edit_entry("e1", None, edited.to_str().unwrap(), "a", "b", Some("a\n"), &[hunk(1, 1, &["-a", "+b"])]),
+    ]);
+
+    // Run from outside the repository: the transcript's cwd decides.
+    let patch = export_patch(&transcript, std::path::Path::new("/"));
+    let _ = fs::remove_dir_all(&repo);
+    assert_eq!(patch, "--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1 +1 @@\n-a\n+b\n");
+}
+
+#[test]
+fn test_export_patch_outside_repo_keeps_absolute_path() {
+    let file = std::env::temp_dir().join(format!("patch_export_abs_{}.txt", unique_id()));
+    fs::write(&file, "b\n").unwrap();
+    let transcript = create_test_transcript(&[
+        edit_entry("e1", None, file.to_str().unwrap(), "a", "b", Some("a\n"), &[hunk(1, 1, &["-a", "+b"])]),
+    ]);
+
+    let patch = export_patch(&transcript, std::path::Path::new("/"));
+    let _ = fs::remove_file(&file);
+    let path = file.to_str().unwrap();
+    assert_eq!(patch, format!("--- {path}\n+++ {path}\n@@ -1 +1 @@\n-a\n+b\n"));
+}
+
+#[test]
+fn test_export_patch_reverted_file_is_omitted() {
+    let file = std::env::temp_dir().join(format!("patch_export_revert_{}.txt", unique_id()));
+    fs::write(&file, "a\n").unwrap();
+    let transcript = create_test_transcript(&[
+        edit_entry("e1", None, file.to_str().unwrap(), "a", "b", Some("a\n"), &[hunk(1, 1, &["-a", "+b"])]),
+        edit_entry("e2", Some("e1"), file.to_str().unwrap(), "b", "a", Some("b\n"), &[hunk(1, 1, &["-b", "+a"])]),
+    ]);
+
+    let patch = export_patch(&transcript, std::path::Path::new("/"));
+    let _ = fs::remove_file(&file);
+    assert_eq!(patch, "");
+}
+
+fn append_entries(transcript_path: &str, entries: &[String]) {
+    let mut file = fs::OpenOptions::new().append(true).open(transcript_path).unwrap();
+    for entry in entries {
+        writeln!(file, "{}", entry).unwrap();
+    }
+}
+
+#[test]
+fn test_incremental_append_matches_full_read() {
+    // Render, append another edit to the same file, render again: the second
+    // render only reads the new record but must land on the full-read answer.
+    let test_file = std::env::temp_dir().join(format!("patch_incr_append_{}.txt", unique_id()));
+    let path = test_file.to_str().unwrap();
+    fs::write(&test_file, "a\nB\nC\n").unwrap();
+    let transcript = create_test_transcript(&[
+        edit_entry("e1", None, path, "b", "B", Some("a\nb\nc\n"), &[hunk(1, 1, &[" a", "-b", "+B", " c"])]),
+    ]);
+    assert_eq!(render_diff(&transcript), (1, 1));
+
+    append_entries(&transcript, &[
+        edit_entry("e2", Some("e1"), path, "c", "C", Some("a\nB\nc\n"), &[hunk(1, 1, &[" a", " B", "-c", "+C"])]),
+    ]);
+    let (added, removed) = render_diff(&transcript);
+    let _ = fs::remove_file(&test_file);
+    assert_eq!((added, removed), (2, 2));
+}
+
+#[test]
+fn test_incremental_rewind_drops_abandoned_edits() {
+    // After a render, the session is rewound to before the second edit: the
+    // new branch hangs off m1, abandoning e2, which was already counted.
+    let test_file = std::env::temp_dir().join(format!("patch_incr_rewind_{}.txt", unique_id()));
+    let path = test_file.to_str().unwrap();
+    fs::write(&test_file, "A\nb\n").unwrap();
+    let transcript = create_test_transcript(&[
+        edit_entry("e1", None, path, "a", "A", Some("a\nb\n"), &[hunk(1, 1, &["-a", "+A", " b"])]),
+        message_entry("m1", Some("e1")),
+        edit_entry("e2", Some("m1"), path, "b", "B", Some("A\nb\n"), &[hunk(1, 1, &[" A", "-b", "+B"])]),
+    ]);
+    assert_eq!(render_diff(&transcript), (2, 2));
+
+    append_entries(&transcript, &[message_entry("m2", Some("m1"))]);
+    let (added, removed) = render_diff(&transcript);
+    let _ = fs::remove_file(&test_file);
+    assert_eq!((added, removed), (1, 1));
+}
+
+#[test]
+fn test_incremental_branch_rebuilds_from_the_whole_tree() {
+    // Only the tip is kept between renders. An entry hanging off e1 doesn't
+    // continue from it, so the next render re-reads the whole tree, where
+    // the branch is abandoned once the conversation carries on from e2.
+    let test_file = std::env::temp_dir().join(format!("patch_incr_branch_{}.txt", unique_id()));
+    let path = test_file.to_str().unwrap();
+    fs::write(&test_file, "A\nB\n").unwrap();
+    let transcript = create_test_transcript(&[
+        edit_entry("e1", None, path, "a", "A", Some("a\nb\n"), &[hunk(1, 1, &["-a", "+A", " b"])]),
+        edit_entry("e2", Some("e1"), path, "b", "B", Some("A\nb\n"), &[hunk(1, 1, &[" A", "-b", "+B"])]),
+    ]);
+    assert_eq!(render_diff(&transcript), (2, 2));
+    let stem = std::path::Path::new(&transcript).file_stem().unwrap().to_string_lossy().into_owned();
+    let state = fs::read_to_string(cache_home(&transcript).join(format!("statusline/diff-{stem}.json"))).unwrap();
+
+    append_entries(&transcript, &[
+        edit_entry("s1", Some("e1"), path, "B", "X", Some("A\nB\n"), &[hunk(1, 1, &[" A", "-B", "+X"])]),
+        message_entry("m1", Some("e2")),
+    ]);
+    let (added, removed) = render_diff(&transcript);
+    let _ = fs::remove_file(&test_file);
+    assert!(!state.contains("\"e1\""), "{state}");
+    assert_eq!((added, removed), (2, 2));
+}
+
+#[test]
+fn test_incremental_partial_line_is_read_once_complete() {
+    let test_file = std::env::temp_dir().join(format!("patch_incr_partial_{}.txt", unique_id()));
+    let path = test_file.to_str().unwrap();
+    fs::write(&test_file, "A\nB\n").unwrap();
+    let first = edit_entry("e1", None, path, "a", "A", Some("a\nb\n"), &[hunk(1, 1, &["-a", "+A", " b"])]);
+    let second = edit_entry("e2", Some("e1"), path, "b", "B", Some("A\nb\n"), &[hunk(1, 1, &[" A", "-b", "+B"])]);
+    let transcript = create_test_transcript(&[first]);
+
+    // Half of the next line has been flushed when the render runs.
+    let (head, tail) = second.split_at(second.len() / 2);
+    let mut file = fs::OpenOptions::new().append(true).open(&transcript).unwrap();
+    write!(file, "{}", head).unwrap();
+    assert_eq!(render_diff(&transcript), (1, 1));
+
+    writeln!(file, "{}", tail).unwrap();
+    let (added, removed) = render_diff(&transcript);
+    let _ = fs::remove_file(&test_file);
+    assert_eq!((added, removed), (2, 2));
+}
+
+/// Assistant entry running `command` through the Bash tool from `cwd`.
+fn bash_entry(uuid: &str, parent: Option<&str>, cwd: &std::path::Path, command: &str) -> String {
+    let obj = json!({
+        "uuid": uuid,
+        "parentUuid": parent,
+        "type": "assistant",
+        "cwd": cwd,
+        "message": {
+            "role": "assistant",
+            "content": [{ "type": "tool_use", "id": "toolu_1", "name": "Bash", "input": { "command": command } }],
+        },
+    });
+    serde_json::to_string(&obj).unwrap()
+}
+
+fn temp_session_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("patch_bash_{}_{}_{}", name, std::process::id(), unique_id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_bash_rm_of_edited_file_counts_its_prior_lines() {
+    let dir = temp_session_dir("rm");
+    let file = dir.join("old.txt");
+    let transcript = create_test_transcript(&[
+        edit_entry("e1", None, file.to_str().unwrap(), "b", "B", Some("a\nb\nc\n"),
+            &[hunk(1, 1, &[" a", "-b", "+B", " c"])]),
+        bash_entry("b1", Some("e1"), &dir, "rm -f old.txt 2>/dev/null && echo done"),
+    ]);
+
+    let counts = render_diff(&transcript);
+    let _ = fs::remove_dir_all(&dir);
+    assert_eq!(counts, (0, 3));
+}
+
+#[test]
+fn test_bash_rm_of_created_file_nets_zero() {
+    let dir = temp_session_dir("rm_new");
+    fs::write(dir.join("keep.txt"), "k\n").unwrap();
+    let transcript = create_test_transcript(&[
+        write_entry("w1", None, dir.join("scratch.py").to_str().unwrap(), None, "x\ny\nz\n", &[]),
+        write_entry("w2", Some("w1"), dir.join("keep.txt").to_str().unwrap(), None, "k\n", &[]),
+        bash_entry("b1", Some("w2"), &dir, "cd sub/.. && rm *.py"),
+    ]);
+
+    let counts = render_diff(&transcript);
+    let _ = fs::remove_dir_all(&dir);
+    assert_eq!(counts, (1, 0));
+}
+
+#[test]
+fn test_bash_mv_carries_history_to_new_path() {
+    let dir = temp_session_dir("mv");
+    let (from, to) = (dir.join("a.txt"), dir.join("b.txt"));
+    fs::write(&to, "x\nY\nz\n").unwrap();
+    let transcript = create_test_transcript(&[
+        write_entry("w1", None, from.to_str().unwrap(), Some(""), "x\ny\nz\n", &[]),
+        bash_entry("b1", Some("w1"), &dir, "git mv a.txt b.txt"),
+        edit_entry("e1", Some("b1"), to.to_str().unwrap(), "y", "Y", Some("x\ny\nz\n"),
+            &[hunk(1, 1, &[" x", "-y", "+Y", " z"])]),
+    ]);
+
+    let counts = render_diff(&transcript);
+    let _ = fs::remove_dir_all(&dir);
+    assert_eq!(counts, (3, 0));
+}
+
+#[test]
+fn test_bash_mv_with_redirections_keeps_its_destination() {
+    let dir = temp_session_dir("mv_redirect");
+    let (from, to) = (dir.join("old.txt"), dir.join("moved.txt"));
+    fs::write(&to, "x\ny\n").unwrap();
+    let transcript = create_test_transcript(&[
+        write_entry("w1", None, from.to_str().unwrap(), Some(""), "x\ny\n", &[]),
+        bash_entry("b1", Some("w1"), &dir, "mv old.txt tmp.txt 2>/dev/null && mv tmp.txt moved.txt 2>&1 | cat"),
+    ]);
+
+    let counts = render_diff(&transcript);
+    let patch = export_patch(&transcript, &dir);
+    let _ = fs::remove_dir_all(&dir);
+    assert_eq!(counts, (2, 0));
+    assert!(patch.contains(&format!("+++ {}\n", to.display())) && !patch.contains("/2/"), "{patch}");
+}
+
+#[test]
+fn test_export_patch_bash_rename_and_delete() {
+    let repo = temp_session_dir("export");
+    fs::create_dir_all(repo.join(".git")).unwrap();
+    fs::write(repo.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+    fs::create_dir_all(repo.join("sub")).unwrap();
+    fs::write(repo.join("sub/moved.txt"), "one\nTWO\n").unwrap();
+    let transcript = create_test_transcript(&[
+        edit_entry("e1", None, repo.join("moved.txt").to_str().unwrap(), "two", "TWO", Some("one\ntwo\n"),
+            &[hunk(1, 1, &[" one", "-two", "+TWO"])]),
+        edit_entry("e2", Some("e1"), repo.join("gone.txt").to_str().unwrap(), "a", "b", Some("a\n"),
+            &[hunk(1, 1, &["-a", "+b"])]),
+        bash_entry("b1", Some("e2"), &repo, "git mv moved.txt sub && git rm -q gone.txt"),
+    ]);
+
+    let patch = export_patch(&transcript, &repo);
+    let _ = fs::remove_dir_all(&repo);
+    assert_eq!(patch, concat!(
+        "--- a/moved.txt\n",
+        "+++ b/sub/moved.txt\n",
+        "@@ -1,2 +1,2 @@\n",
+        " one\n",
+        "-two\n",
+        "+TWO\n",
+        "--- a/gone.txt\n",
+        "+++ /dev/null\n",
+        "@@ -1 +0,0 @@\n",
+        "-a\n",
+    ));
+}
@@ -0,0 +1,118 @@
+//! End-to-end tests for the `path` field: the cwd relative to the repository
+//! top level, with the configurable shortening styles.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde_json::{Value, json};
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+fn unique_id() -> u64 {
+    COUNTER.fetch_add(1, Ordering::SeqCst)
+}
+
+/// A fake repository named `monorepo` with `packages/api/src` inside it.
+fn setup_monorepo() -> (PathBuf, PathBuf) {
+    let base = std::env::temp_dir().join(format!("sl_path_{}_{}", std::process::id(), unique_id()));
+    let root = base.join("monorepo");
+    fs::create_dir_all(root.join(".git")).unwrap();
+    fs::write(root.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+    let src = root.join("packages/api/src");
+    fs::create_dir_all(&src).unwrap();
+    (base, src)
+}
+
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::new();
+    let mut in_escape = false;
+    for c in s.chars() {
+        if c == '\x1b' {
+            in_escape = true;
+        } else if in_escape {
+            in_escape = c != 'm';
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Renders just the `path` field with the given `path` config section.
+fn run_path(cwd: &Path, path_cfg: Value) -> String {
+    let home = std::env::temp_dir().join(format!("sl_path_home_{}_{}", std::process::id(), unique_id()));
+    fs::create_dir_all(home.join(".claude")).unwrap();
+    let config = json!({ "lines": [{ "fields": ["path"], "separator": "|" }], "path": path_cfg });
+    fs::write(home.join(".claude/statusline.json"), config.to_string()).unwrap();
+    let transcript = home.join("t.jsonl");
+    fs::write(&transcript, "").unwrap();
+    let input = json!({ "cwd": cwd, "transcript_path": transcript, "model": { "display_name": "test" } });
+
+    let output = Command::new(env!("CARGO_BIN_EXE_statusline"))
+        .env("HOME", &home)
+        .env("XDG_CACHE_HOME", home.join(".cache"))
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            child.stdin.as_mut().unwrap().write_all(input.to_string().as_bytes())?;
+            child.wait_with_output()
+        })
+        .expect("Failed to run statusline");
+    let _ = fs::remove_dir_all(&home);
+    strip_ansi(&String::from_utf8_lossy(&output.stdout)).trim().to_string()
+}
+
+#[test]
+fn test_path_relative_to_repo_root() {
+    let (base, src) = setup_monorepo();
+    assert_eq!(run_path(&src, json!({})), "monorepo/packages/api/src");
+    assert_eq!(run_path(&base.join("monorepo"), json!({})), "monorepo");
+    let _ = fs::remove_dir_all(&base);
+}
+
+#[test]
+fn test_path_fish_style() {
+    let (base, src) = setup_monorepo();
+    assert_eq!(run_path(&src, json!({ "style": "fish" })), "m/p/api/src");
+    assert_eq!(run_path(&src, json!({ "style": "fish", "keep": 1 })), "m/p/a/src");
+    let _ = fs::remove_dir_all(&base);
+}
+
+#[test]
+fn test_path_fish_keeps_dot_prefix() {
+    let (base, src) = setup_monorepo();
+    let dotted = src.join(".config/nested");
+    fs::create_dir_all(&dotted).unwrap();
+    assert_eq!(run_path(&dotted, json!({ "style": "fish", "keep": 1 })), "m/p/a/s/.c/nested");
+    let _ = fs::remove_dir_all(&base);
+}
+
+#[test]
+fn test_path_last_components() {
+    let (base, src) = setup_monorepo();
+    assert_eq!(run_path(&src, json!({ "style": "last" })), "…/api/src");
+    assert_eq!(run_path(&src, json!({ "style": "last", "keep": 10 })), "monorepo/packages/api/src");
+    let _ = fs::remove_dir_all(&base);
+}
+
+#[test]
+fn test_path_max_width() {
+    let (base, src) = setup_monorepo();
+    assert_eq!(run_path(&src, json!({ "max_width": 12 })), "…ges/api/src");
+    assert_eq!(run_path(&src, json!({ "style": "fish", "max_width": 40 })), "m/p/api/src");
+    let _ = fs::remove_dir_all(&base);
+}
+
+#[test]
+fn test_path_outside_repo() {
+    let base = std::env::temp_dir().join(format!("sl_path_norepo_{}_{}", std::process::id(), unique_id()));
+    let dir = base.join("a/b");
+    fs::create_dir_all(&dir).unwrap();
+    let expected = format!("{}", dir.display());
+    assert_eq!(run_path(&dir, json!({})), expected);
+    let _ = fs::remove_dir_all(&base);
+}
@@ -0,0 +1,184 @@
+use serde_json::json;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static TEST_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+fn unique_id() -> u64 {
+    TEST_COUNTER.fetch_add(1, Ordering::SeqCst)
+}
+
+/// A sandbox HOME with a fake repository (`<home>/repo`, a `.git/HEAD` is
+/// enough to be found) and a config showing `diff-today`.
+fn sandbox() -> (PathBuf, PathBuf) {
+    let home = std::env::temp_dir().join(format!("test_project_diff_{}_{}", std::process::id(), unique_id()));
+    let repo = home.join("repo");
+    fs::create_dir_all(repo.join(".git")).unwrap();
+    fs::write(repo.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+    let config = json!({
+        "lines": [{ "fields": ["diff-today"], "separator": "|" }],
+        "track_usage": { "timezone": "UTC" },
+    });
+    fs::create_dir_all(home.join(".claude")).unwrap();
+    fs::write(home.join(".claude/statusline.json"), config.to_string()).unwrap();
+    (home, repo)
+}
+
+/// An Edit record made from `cwd` at `ts`.
+fn edit(cwd: &Path, file: &Path, old: &str, new: &str, ts: &str) -> String {
+    json!({
+        "cwd": cwd,
+        "timestamp": ts,
+        "toolUseResult": { "filePath": file, "oldString": old, "newString": new },
+    })
+    .to_string()
+}
+
+/// Writes a session transcript under `~/.claude/projects/<project>/`.
+fn write_session(home: &Path, project: &str, lines: &[String]) -> PathBuf {
+    let dir = home.join(".claude/projects").join(project);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(format!("session-{}-{}.jsonl", std::process::id(), unique_id()));
+    let mut f = fs::File::create(&path).unwrap();
+    for l in lines {
+        writeln!(f, "{l}").unwrap();
+    }
+    path
+}
+
+fn run_summary(home: &Path, cwd: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_statusline"))
+        .arg("--diff-summary")
+        .args(args)
+        .current_dir(cwd)
+        .env("HOME", home)
+        .env("XDG_CACHE_HOME", home.join(".cache"))
+        .output()
+        .expect("Failed to run statusline")
+}
+
+fn render(home: &Path, cwd: &Path, transcript: &Path) -> String {
+    let input = json!({
+        "cwd": cwd,
+        "transcript_path": transcript,
+        "model": { "display_name": "test" },
+    });
+    let output = Command::new(env!("CARGO_BIN_EXE_statusline"))
+        .env("HOME", home)
+        .env("XDG_CACHE_HOME", home.join(".cache"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            child.stdin.as_mut().unwrap().write_all(input.to_string().as_bytes())?;
+            child.wait_with_output()
+        })
+        .expect("Failed to run statusline");
+    let mut out = String::new();
+    let mut in_escape = false;
+    for c in String::from_utf8_lossy(&output.stdout).chars() {
+        if c == '\x1b' {
+            in_escape = true;
+        } else if in_escape {
+            in_escape = c != 'm';
+        } else {
+            out.push(c);
+        }
+    }
+    out.trim().to_string()
+}
+
+/// Two sessions in the repository: the first edits `a.txt` and `b.txt`, the
+/// second undoes its `a.txt` edit. A third session runs elsewhere, and an
+/// edit from long ago predates every period.
+fn setup_sessions(home: &Path, repo: &Path) -> PathBuf {
+    let a = repo.join("a.txt");
+    let b = repo.join("src/b.txt");
+    fs::create_dir_all(repo.join("src")).unwrap();
+    fs::write(&a, "old\n").unwrap();
+    fs::write(&b, "one\ntwo\n").unwrap();
+    let elsewhere = home.join("elsewhere");
+    fs::create_dir_all(&elsewhere).unwrap();
+    let other = elsewhere.join("c.txt");
+    fs::write(&other, "new\n").unwrap();
+
+    write_session(home, "repo", &[
+        edit(repo, &a, "old\n", "new\n", "2099-01-01T10:00:00Z"),
+        edit(repo, &b, "one\n", "one\ntwo\n", "2099-01-01T10:01:00Z"),
+    ]);
+    write_session(home, "elsewhere", &[edit(&elsewhere, &other, "old\n", "new\n", "2099-01-01T10:02:00Z")]);
+    write_session(home, "repo", &[
+        edit(&repo.join("src"), &b, "x\n", "one\n", "2000-01-01T00:00:00Z"),
+        edit(&repo.join("src"), &a, "new\n", "old\n", "2099-01-01T11:00:00Z"),
+    ])
+}
+
+#[test]
+fn test_diff_summary_nets_edits_across_sessions() {
+    let (home, repo) = sandbox();
+    setup_sessions(&home, &repo);
+
+    let out = run_summary(&home, &repo, &[]);
+    let _ = fs::remove_dir_all(&home);
+    assert!(out.status.success());
+    let lines: Vec<String> = String::from_utf8_lossy(&out.stdout).lines().map(String::from).collect();
+    assert_eq!(lines, vec!["+1 -0  src/b.txt", "+1 -0  1 file in 2 sessions today"]);
+}
+
+#[test]
+fn test_diff_summary_since_week() {
+    let (home, repo) = sandbox();
+    setup_sessions(&home, &repo);
+
+    let out = run_summary(&home, &repo.join("src"), &["--since", "week"]);
+    let _ = fs::remove_dir_all(&home);
+    let stdout = String::from_utf8_lossy(&out.stdout).to_string();
+    assert_eq!(stdout.lines().last(), Some("+1 -0  1 file in 2 sessions this week"));
+}
+
+#[test]
+fn test_diff_summary_rejects_unknown_period() {
+    let (home, repo) = sandbox();
+    let out = run_summary(&home, &repo, &["--since", "month"]);
+    let _ = fs::remove_dir_all(&home);
+    assert_eq!(out.status.code(), Some(2));
+}
+
+#[test]
+fn test_diff_today_field() {
+    let (home, repo) = sandbox();
+    let current = setup_sessions(&home, &repo);
+
+    let first = render(&home, &repo, &current);
+    // A further edit in another session shows up on the next render.
+    let c = repo.join("c.txt");
+    fs::write(&c, "1\n2\n").unwrap();
+    write_session(&home, "repo", &[edit(&repo, &c, "", "1\n2\n", "2099-01-01T12:00:00Z")]);
+    let second = render(&home, &repo, &current);
+    let _ = fs::remove_dir_all(&home);
+    assert_eq!(first, "today +1 -0");
+    assert_eq!(second, "today +3 -0");
+}
+
+#[test]
+fn test_diff_today_reads_appended_edits() {
+    let (home, repo) = sandbox();
+    let current = setup_sessions(&home, &repo);
+
+    let first = render(&home, &repo, &current);
+    // The session goes on to edit b.txt again, and --diff-summary, which
+    // shares the records read so far, sees it too.
+    let b = repo.join("src/b.txt");
+    fs::write(&b, "one\ntwo\nthree\n").unwrap();
+    let mut f = fs::OpenOptions::new().append(true).open(&current).unwrap();
+    writeln!(f, "{}", edit(&repo, &b, "two\n", "two\nthree\n", "2099-01-01T12:00:00Z")).unwrap();
+    let second = render(&home, &repo, &current);
+    let summary = run_summary(&home, &repo, &[]);
+    let _ = fs::remove_dir_all(&home);
+    assert_eq!((first.as_str(), second.as_str()), ("today +1 -0", "today +2 -0"));
+    let stdout = String::from_utf8_lossy(&summary.stdout).to_string();
+    assert_eq!(stdout.lines().last(), Some("+2 -0  1 file in 2 sessions today"));
+}
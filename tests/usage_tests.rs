@@ -103,6 +103,7 @@ fn run(home: &Path) {
     let input = r#"{"cwd":"/tmp","transcript_path":"/tmp/none","model":{"display_name":"test"}}"#;
     let output = Command::new(env!("CARGO_BIN_EXE_statusline"))
         .env("HOME", home)
+        .env("XDG_CACHE_HOME", home.join(".cache"))
         .stdin(std::process::Stdio::piped())
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped())
@@ -0,0 +1,229 @@
+//! End-to-end tests for the jj and hg backends behind the `dir` and `branch`
+//! fields. Neither tool is needed: stand-in `jj`/`hg` scripts are put on PATH.
+
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde_json::json;
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+fn temp_dir(name: &str) -> PathBuf {
+    let id = COUNTER.fetch_add(1, Ordering::SeqCst);
+    let dir = std::env::temp_dir().join(format!("sl_vcs_{}_{}_{}", name, std::process::id(), id));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// A directory holding an executable `name` that prints `stdout`.
+fn fake_tool(name: &str, stdout: &str) -> PathBuf {
+    let bin = temp_dir("bin");
+    let script = bin.join(name);
+    fs::write(&script, format!("#!/bin/sh\nprintf '{}'\n", stdout)).unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+    bin
+}
+
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\x1b', Some('[')) => {
+                for n in chars.by_ref() {
+                    if n == 'm' { break; }
+                }
+            }
+            ('\x1b', Some(']')) => {
+                while let Some(n) = chars.next() {
+                    if n == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Renders `dir` and `branch` with `bin` as the only PATH entry, returning
+/// the raw output.
+fn run_statusline(cwd: &Path, bin: &Path) -> String {
+    let home = temp_dir("home");
+    let out = run_in_home(cwd, bin, &home);
+    let _ = fs::remove_dir_all(&home);
+    out
+}
+
+/// `run_statusline` with a caller-owned `$HOME`, so repeated renders share
+/// one session and its cache.
+fn run_in_home(cwd: &Path, bin: &Path, home: &Path) -> String {
+    run_fields(cwd, bin, home, &["dir", "branch"])
+}
+
+fn run_fields(cwd: &Path, bin: &Path, home: &Path, fields: &[&str]) -> String {
+    fs::create_dir_all(home.join(".claude")).unwrap();
+    let config = json!({ "lines": [{ "fields": fields, "separator": "|" }] });
+    fs::write(home.join(".claude/statusline.json"), config.to_string()).unwrap();
+    let transcript = home.join(format!("{}.jsonl", home.file_name().unwrap().to_str().unwrap()));
+    fs::write(&transcript, "").unwrap();
+    let input = json!({ "cwd": cwd, "transcript_path": transcript, "model": { "display_name": "test" } });
+
+    let output = Command::new(env!("CARGO_BIN_EXE_statusline"))
+        .env("HOME", home)
+        .env("XDG_CACHE_HOME", home.join(".cache"))
+        .env("PATH", bin)
+        .env_remove("XDG_CONFIG_HOME")
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            child.stdin.as_mut().unwrap().write_all(input.to_string().as_bytes())?;
+            child.wait_with_output()
+        })
+        .expect("Failed to run statusline");
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+fn branch_of(cwd: &Path, bin: &Path) -> String {
+    let out = strip_ansi(&run_statusline(cwd, bin));
+    out.trim().split(" | ").nth(1).unwrap_or("").to_string()
+}
+
+fn first_link(raw: &str) -> Option<String> {
+    let start = raw.find("\x1b]8;;")? + 5;
+    let end = raw[start..].find("\x1b\\")?;
+    Some(raw[start..start + end].to_string()).filter(|u| !u.is_empty())
+}
+
+fn setup_hg(branch: Option<&str>) -> PathBuf {
+    let root = temp_dir("hg");
+    fs::create_dir_all(root.join(".hg")).unwrap();
+    if let Some(b) = branch {
+        fs::write(root.join(".hg/branch"), format!("{}\n", b)).unwrap();
+    }
+    root
+}
+
+#[test]
+fn test_hg_branch_and_remote() {
+    let root = setup_hg(Some("stable"));
+    fs::write(root.join(".hg/hgrc"), "[ui]\nusername = x\n[paths]\ndefault = https://hg.example.com/owner/project\n").unwrap();
+    fs::create_dir_all(root.join("src")).unwrap();
+    let bin = fake_tool("hg", "");
+
+    assert_eq!(branch_of(&root.join("src"), &bin), "stable");
+    assert_eq!(first_link(&run_statusline(&root, &bin)).as_deref(), Some("https://hg.example.com/owner/project"));
+}
+
+#[test]
+fn test_hg_default_branch_dirty() {
+    let root = setup_hg(None);
+    let bin = fake_tool("hg", "M a.txt\n");
+    assert_eq!(branch_of(&root, &bin), "default*");
+}
+
+/// A directory holding an executable `name` that prints `stdout` and
+/// appends a line to the returned `calls` file each time it runs.
+fn counting_tool(name: &str, stdout: &str) -> (PathBuf, PathBuf) {
+    let bin = temp_dir("bin");
+    let calls = bin.join("calls");
+    let script = bin.join(name);
+    fs::write(&script, format!("#!/bin/sh\necho x >> '{}'\nprintf '{}'\n", calls.display(), stdout)).unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+    (bin, calls)
+}
+
+fn call_count(calls: &Path) -> usize {
+    fs::read_to_string(calls).map(|s| s.lines().count()).unwrap_or(0)
+}
+
+#[test]
+fn test_hg_dirty_check_is_cached() {
+    let root = setup_hg(None);
+    let (bin, calls) = counting_tool("hg", "M a.txt\\n");
+    let home = temp_dir("home");
+
+    let first = strip_ansi(&run_in_home(&root, &bin, &home));
+    let second = strip_ansi(&run_in_home(&root, &bin, &home));
+    let _ = fs::remove_dir_all(&home);
+    assert!(first.contains("default*") && second.contains("default*"), "{first} / {second}");
+    assert_eq!(call_count(&calls), 1);
+}
+
+#[test]
+fn test_hg_dirty_check_only_for_branch() {
+    let root = setup_hg(None);
+    let (bin, calls) = counting_tool("hg", "M a.txt\\n");
+    let home = temp_dir("home");
+    run_fields(&root, &bin, &home, &["dir", "diff"]);
+    let _ = fs::remove_dir_all(&home);
+    assert_eq!(call_count(&calls), 0);
+}
+
+#[test]
+fn test_hg_active_bookmark_wins() {
+    let root = setup_hg(Some("default"));
+    fs::write(root.join(".hg/bookmarks.current"), "feature").unwrap();
+    // No hg on PATH: the dirty check fails quietly and no marker is shown.
+    let bin = temp_dir("empty");
+    assert_eq!(branch_of(&root, &bin), "feature");
+}
+
+/// A colocated jj workspace: `.jj` next to the `.git` it stores commits in.
+fn setup_jj() -> PathBuf {
+    let root = temp_dir("jj");
+    fs::create_dir_all(root.join(".git")).unwrap();
+    fs::write(root.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+    fs::write(root.join(".git/config"), "[remote \"origin\"]\n\turl = git@github.com:owner/repo.git\n").unwrap();
+    fs::create_dir_all(root.join(".jj/repo/store")).unwrap();
+    fs::write(root.join(".jj/repo/store/git_target"), "../../../.git").unwrap();
+    root
+}
+
+#[test]
+fn test_jj_bookmark_on_parent_and_dirty() {
+    let root = setup_jj();
+    let bin = fake_tool("jj", "kxqpmrlu\\t\\tmain\\t1\\n");
+    assert_eq!(branch_of(&root, &bin), "main*");
+    assert_eq!(first_link(&run_statusline(&root, &bin)).as_deref(), Some("https://github.com/owner/repo"));
+}
+
+#[test]
+fn test_jj_own_bookmark_clean() {
+    let root = setup_jj();
+    let bin = fake_tool("jj", "kxqpmrlu\\tfeature\\tmain\\t0\\n");
+    assert_eq!(branch_of(&root, &bin), "feature");
+}
+
+#[test]
+fn test_jj_anonymous_change() {
+    let root = setup_jj();
+    let bin = fake_tool("jj", "kxqpmrlu\\t\\t\\t0\\n");
+    assert_eq!(branch_of(&root, &bin), "@kxqpmrlu");
+}
+
+#[test]
+fn test_jj_log_is_cached_until_a_new_operation() {
+    let root = setup_jj();
+    fs::create_dir_all(root.join(".jj/repo/op_heads/heads")).unwrap();
+    let (bin, calls) = counting_tool("jj", "kxqpmrlu\\tfeature\\tmain\\t0\\n");
+    let home = temp_dir("home");
+
+    let first = strip_ansi(&run_in_home(&root, &bin, &home));
+    run_in_home(&root, &bin, &home);
+    let cached = call_count(&calls);
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    fs::write(root.join(".jj/repo/op_heads/heads/abc"), "").unwrap();
+    run_in_home(&root, &bin, &home);
+    let _ = fs::remove_dir_all(&home);
+    assert!(first.contains("feature"), "{first}");
+    assert_eq!((cached, call_count(&calls)), (1, 2));
+}